@@ -0,0 +1,266 @@
+//! Somewhere for the plugin to draw cells and pixels, that is then sent to Tattoy as a single
+//! frame.
+
+/// Whether a pixel is in the upper or lower half of its cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Half {
+    /// The upper half of the cell, rendered with "▀".
+    Upper,
+    /// The lower half of the cell, rendered with "▄".
+    Lower,
+}
+
+/// Convert pixel coordinates to the coordinates of the cell that contains the pixel. There are 2
+/// pixels in every cell, so the y-axis is twice as long as the number of rows in the terminal.
+#[inline]
+#[must_use]
+#[expect(
+    clippy::integer_division,
+    clippy::integer_division_remainder_used,
+    reason = "Both pixels in a cell should map to the same row"
+)]
+pub const fn pixel_to_cell(x: u32, y: u32) -> ((u32, u32), Half) {
+    let half = if y.is_multiple_of(2) {
+        Half::Upper
+    } else {
+        Half::Lower
+    };
+    ((x, y / 2), half)
+}
+
+/// Make a cell that renders 2 vertically stacked pixels, using the UTF8 half-block trick: ▀▄.
+/// Returns `None` if neither pixel has a colour.
+#[inline]
+#[must_use]
+pub fn half_block_cell(
+    coordinates: (u32, u32),
+    upper: Option<tattoy_protocol::Colour>,
+    lower: Option<tattoy_protocol::Colour>,
+) -> Option<tattoy_protocol::Cell> {
+    let (character, fg, bg) = match (upper, lower) {
+        (Some(upper_colour), _) => ('▀', upper_colour, lower),
+        (None, Some(lower_colour)) => ('▄', lower_colour, None),
+        (None, None) => return None,
+    };
+
+    Some(
+        tattoy_protocol::Cell::builder()
+            .character(character)
+            .coordinates(coordinates)
+            .fg(fg)
+            .maybe_bg(bg)
+            .build(),
+    )
+}
+
+/// A frame of cells and pixels. Cells are drawn on top of pixels.
+#[derive(Clone, Debug)]
+pub struct Canvas {
+    /// The width in columns.
+    width: u16,
+    /// The height in rows.
+    height: u16,
+    /// Every cell in the canvas, row by row. `None` is a blank cell.
+    cells: Vec<Option<tattoy_protocol::Cell>>,
+    /// Every pixel in the canvas, row by row. There are twice as many rows of pixels as there are
+    /// rows of cells.
+    pixels: Vec<Option<tattoy_protocol::Colour>>,
+}
+
+impl Canvas {
+    /// Make a blank canvas of the given size in columns and rows. Normally the same size as the
+    /// user's terminal.
+    #[inline]
+    #[must_use]
+    pub fn new(width: u16, height: u16) -> Self {
+        let area = usize::from(width) * usize::from(height);
+        Self {
+            width,
+            height,
+            cells: vec![None; area],
+            pixels: vec![None; area * 2],
+        }
+    }
+
+    /// The width in columns.
+    #[inline]
+    #[must_use]
+    pub const fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// The height in rows.
+    #[inline]
+    #[must_use]
+    pub const fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// The height in pixels, which is twice the number of rows.
+    #[inline]
+    #[must_use]
+    pub fn pixel_height(&self) -> u32 {
+        u32::from(self.height) * 2
+    }
+
+    /// Blank the whole canvas.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.cells.fill(None);
+        self.pixels.fill(None);
+    }
+
+    /// Draw a cell at its own coordinates. Returns `false` if the cell is outside the canvas.
+    #[inline]
+    pub fn set_cell(&mut self, cell: tattoy_protocol::Cell) -> bool {
+        let Some(index) = Self::index(self.width, self.height, cell.coordinates) else {
+            return false;
+        };
+        let Some(slot) = self.cells.get_mut(index) else {
+            return false;
+        };
+        *slot = Some(cell);
+        true
+    }
+
+    /// Draw text starting at the given coordinates. Any text beyond the right edge of the canvas
+    /// is dropped.
+    #[inline]
+    pub fn set_text(
+        &mut self,
+        x: u32,
+        y: u32,
+        text: &str,
+        fg: Option<tattoy_protocol::Colour>,
+        bg: Option<tattoy_protocol::Colour>,
+    ) {
+        for (column, character) in (x..).zip(text.chars()) {
+            let cell = tattoy_protocol::Cell::builder()
+                .character(character)
+                .coordinates((column, y))
+                .maybe_fg(fg)
+                .maybe_bg(bg)
+                .build();
+            if !self.set_cell(cell) {
+                break;
+            }
+        }
+    }
+
+    /// Draw a pixel. Returns `false` if the pixel is outside the canvas.
+    #[inline]
+    pub fn set_pixel(&mut self, x: u32, y: u32, colour: tattoy_protocol::Colour) -> bool {
+        let Some(height) = self.height.checked_mul(2) else {
+            return false;
+        };
+        let Some(index) = Self::index(self.width, height, (x, y)) else {
+            return false;
+        };
+        let Some(slot) = self.pixels.get_mut(index) else {
+            return false;
+        };
+        *slot = Some(colour);
+        true
+    }
+
+    /// Convert the canvas into a single frame for Tattoy.
+    #[inline]
+    #[must_use]
+    pub fn to_message(&self) -> tattoy_protocol::PluginOutputMessages {
+        let mut cells = Vec::new();
+        let width = usize::from(self.width);
+        for (index, maybe_cell) in self.cells.iter().enumerate() {
+            if let Some(cell) = maybe_cell {
+                cells.push(cell.clone());
+                continue;
+            }
+
+            #[expect(
+                clippy::integer_division,
+                clippy::integer_division_remainder_used,
+                reason = "We're converting an index into a row and column"
+            )]
+            let (row, column) = (index / width, index % width);
+            let upper_index = row * 2 * width + column;
+            let upper = self.pixels.get(upper_index).copied().flatten();
+            let lower = self.pixels.get(upper_index + width).copied().flatten();
+            let (Ok(x), Ok(y)) = (u32::try_from(column), u32::try_from(row)) else {
+                continue;
+            };
+            if let Some(cell) = half_block_cell((x, y), upper, lower) {
+                cells.push(cell);
+            }
+        }
+
+        tattoy_protocol::PluginOutputMessages::OutputCells(cells)
+    }
+
+    /// The index of the given coordinates in a grid of the given size, row by row.
+    fn index(width: u16, height: u16, coordinates: (u32, u32)) -> Option<usize> {
+        let x = usize::try_from(coordinates.0).ok()?;
+        let y = usize::try_from(coordinates.1).ok()?;
+        let width_usize = usize::from(width);
+        if x >= width_usize || y >= usize::from(height) {
+            return None;
+        }
+        Some(y * width_usize + x)
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::indexing_slicing, reason = "Tests aren't so strict")]
+mod test {
+    use super::*;
+
+    const RED: tattoy_protocol::Colour = (1.0, 0.0, 0.0, 1.0);
+    const BLUE: tattoy_protocol::Colour = (0.0, 0.0, 1.0, 1.0);
+
+    fn cells(canvas: &Canvas) -> Vec<tattoy_protocol::Cell> {
+        let tattoy_protocol::PluginOutputMessages::OutputCells(cells) = canvas.to_message() else {
+            panic!("Canvas should output cells");
+        };
+        cells
+    }
+
+    #[test]
+    fn pixels_become_half_blocks() {
+        let mut canvas = Canvas::new(2, 1);
+        assert!(canvas.set_pixel(0, 0, RED));
+        assert!(canvas.set_pixel(0, 1, BLUE));
+        assert!(canvas.set_pixel(1, 1, BLUE));
+        assert!(!canvas.set_pixel(1, 2, BLUE));
+
+        let cells = cells(&canvas);
+        assert_eq!(cells.len(), 2);
+
+        let upper = cells.first().unwrap();
+        assert_eq!(upper.character, '▀');
+        assert_eq!(upper.coordinates, (0, 0));
+        assert_eq!(upper.fg, Some(RED));
+        assert_eq!(upper.bg, Some(BLUE));
+
+        let lower = &cells[1];
+        assert_eq!(lower.character, '▄');
+        assert_eq!(lower.coordinates, (1, 0));
+        assert_eq!(lower.fg, Some(BLUE));
+        assert_eq!(lower.bg, None);
+    }
+
+    #[test]
+    fn text_is_drawn_over_pixels() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.set_pixel(0, 0, RED);
+        canvas.set_text(0, 0, "abc", None, None);
+
+        let cells = cells(&canvas);
+        let characters: String = cells.iter().map(|cell| cell.character).collect();
+        assert_eq!(characters, "ab");
+    }
+
+    #[test]
+    fn converting_pixels_to_cells() {
+        assert_eq!(pixel_to_cell(3, 4), ((3, 2), Half::Upper));
+        assert_eq!(pixel_to_cell(3, 5), ((3, 2), Half::Lower));
+    }
+}
@@ -0,0 +1,137 @@
+//! Everything needed to write a Tattoy plugin in Rust.
+//!
+//! Implement the `Plugin` trait and pass it to `run()`. The SDK takes care of the handshake with
+//! Tattoy, reading and writing messages in whichever encoding the user has configured, keeping
+//! track of the state of the user's terminal and calling `Plugin::on_tick()` at a steady frame
+//! rate.
+//!
+//! ```no_run
+//! struct Hello;
+//!
+//! impl tattoy_plugin_sdk::Plugin for Hello {
+//!     fn capabilities(&self) -> tattoy_protocol::Capabilities {
+//!         tattoy_protocol::Capabilities::builder()
+//!             .name("hello".to_owned())
+//!             .build()
+//!     }
+//!
+//!     fn on_pty_update(
+//!         &mut self,
+//!         terminal: &tattoy_plugin_sdk::terminal::Terminal,
+//!         output: &mut tattoy_plugin_sdk::output::Output,
+//!     ) -> tattoy_plugin_sdk::Result<()> {
+//!         let mut canvas = tattoy_plugin_sdk::canvas::Canvas::new(terminal.size.0, terminal.size.1);
+//!         canvas.set_text(0, 0, "Hello", Some((1.0, 1.0, 1.0, 1.0)), None);
+//!         output.send_canvas(&canvas)
+//!     }
+//! }
+//!
+//! fn main() -> tattoy_plugin_sdk::Result<()> {
+//!     tattoy_plugin_sdk::run(Hello)
+//! }
+//! ```
+
+pub mod canvas;
+pub mod output;
+pub mod runner;
+pub mod terminal;
+
+/// Any error from the plugin or the SDK.
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// The result of any plugin or SDK function.
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// A Tattoy plugin. All the callbacks are optional apart from `capabilities()`.
+///
+/// Returning an error from any of the callbacks stops the plugin. Tattoy will then restart it.
+pub trait Plugin {
+    /// What the plugin is capable of and what it wants from Tattoy. It is sent in reply to
+    /// Tattoy's `hello` message. If `protocol_version` isn't set then the SDK sets it to the
+    /// version that the SDK was built against.
+    fn capabilities(&self) -> tattoy_protocol::Capabilities;
+
+    /// Called with the plugin's `[plugins.settings]` table from the user's Tattoy config, before
+    /// `capabilities()` is called. The settings are empty if the user hasn't set any.
+    ///
+    /// # Errors
+    /// Stops the plugin, for instance if the settings are invalid.
+    #[inline]
+    fn configure(&mut self, _settings: &tattoy_protocol::Settings) -> Result<()> {
+        Ok(())
+    }
+
+    /// How many times a second `on_tick()` should be called. `None` means that it is never called,
+    /// which suits plugins that only react to changes in the user's terminal. Plugins compiled to
+    /// WebAssembly can't start the thread needed for ticks, so it is never called for them either.
+    #[inline]
+    fn tick_rate(&self) -> Option<u32> {
+        None
+    }
+
+    /// Called whenever the contents of the user's terminal change.
+    ///
+    /// # Errors
+    /// Stops the plugin.
+    #[inline]
+    fn on_pty_update(
+        &mut self,
+        _terminal: &terminal::Terminal,
+        _output: &mut output::Output,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called whenever the user's terminal is resized.
+    ///
+    /// # Errors
+    /// Stops the plugin.
+    #[inline]
+    fn on_resize(
+        &mut self,
+        _terminal: &terminal::Terminal,
+        _output: &mut output::Output,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called at the rate given by `tick_rate()`. Useful for animations.
+    ///
+    /// # Errors
+    /// Stops the plugin.
+    #[inline]
+    fn on_tick(
+        &mut self,
+        _terminal: &terminal::Terminal,
+        _output: &mut output::Output,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called for every other message from Tattoy, like user input or the scrollback. Messages
+    /// that are newer than the SDK are skipped.
+    ///
+    /// # Errors
+    /// Stops the plugin.
+    #[inline]
+    fn on_message(
+        &mut self,
+        _message: &tattoy_protocol::PluginInputMessages,
+        _terminal: &terminal::Terminal,
+        _output: &mut output::Output,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Run the plugin, talking to Tattoy over STDIN and STDOUT, until Tattoy closes STDIN.
+///
+/// # Errors
+/// If communicating with Tattoy fails or if any of the plugin's callbacks return an error.
+#[inline]
+pub fn run<P: Plugin>(plugin: P) -> Result<()> {
+    runner::Runner::new(plugin).run(
+        std::io::BufReader::new(std::io::stdin()),
+        Box::new(std::io::stdout()),
+    )
+}
@@ -0,0 +1,64 @@
+//! Sending messages to Tattoy.
+
+/// Sends messages to Tattoy, in whichever encoding the user has configured for the plugin.
+pub struct Output {
+    /// The encoding that Tattoy asked for in its `hello` message.
+    encoding: tattoy_protocol::encoding::Encoding,
+    /// Normally the plugin's STDOUT.
+    writer: Box<dyn std::io::Write + Send>,
+}
+
+impl Output {
+    /// Instantiate.
+    pub(crate) fn new(writer: Box<dyn std::io::Write + Send>) -> Self {
+        Self {
+            encoding: tattoy_protocol::encoding::Encoding::default(),
+            writer,
+        }
+    }
+
+    /// Use the encoding that Tattoy asked for.
+    pub(crate) const fn set_encoding(&mut self, encoding: tattoy_protocol::encoding::Encoding) {
+        self.encoding = encoding;
+    }
+
+    /// Send any message to Tattoy.
+    ///
+    /// # Errors
+    /// If the message can't be encoded or written.
+    #[inline]
+    pub fn send(&mut self, message: &tattoy_protocol::PluginOutputMessages) -> crate::Result<()> {
+        let bytes = tattoy_protocol::encoding::encode(message, self.encoding)?;
+        self.writer.write_all(&bytes)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Send the canvas to Tattoy as a single frame. It replaces whatever frame the plugin
+    /// previously sent.
+    ///
+    /// # Errors
+    /// If the frame can't be encoded or written.
+    #[inline]
+    pub fn send_canvas(&mut self, canvas: &crate::canvas::Canvas) -> crate::Result<()> {
+        self.send(&canvas.to_message())
+    }
+
+    /// Show a notification to the user.
+    ///
+    /// # Errors
+    /// If the notification can't be encoded or written.
+    #[inline]
+    pub fn notify(
+        &mut self,
+        title: &str,
+        body: Option<String>,
+        level: tattoy_protocol::NotificationLevel,
+    ) -> crate::Result<()> {
+        self.send(&tattoy_protocol::PluginOutputMessages::Notification {
+            title: title.to_owned(),
+            body,
+            level,
+        })
+    }
+}
@@ -0,0 +1,348 @@
+//! The plugin's event loop.
+
+/// Does the handshake with Tattoy and then passes Tattoy's messages, and regular ticks, to the
+/// plugin.
+pub struct Runner<P: crate::Plugin> {
+    /// The plugin being run.
+    plugin: P,
+    /// The plugin's copy of the user's terminal.
+    terminal: crate::terminal::Terminal,
+}
+
+impl<P: crate::Plugin> Runner<P> {
+    /// Instantiate.
+    #[inline]
+    pub fn new(plugin: P) -> Self {
+        Self {
+            plugin,
+            terminal: crate::terminal::Terminal::default(),
+        }
+    }
+
+    /// Run the plugin until the reader reaches its end. Normally the reader is STDIN and the
+    /// writer is STDOUT, see `crate::run()`.
+    ///
+    /// # Errors
+    /// If communicating with Tattoy fails or if any of the plugin's callbacks return an error.
+    #[inline]
+    pub fn run<Reader: std::io::BufRead + Send + 'static>(
+        mut self,
+        mut reader: Reader,
+        writer: Box<dyn std::io::Write + Send>,
+    ) -> crate::Result<()> {
+        let mut output = crate::output::Output::new(writer);
+
+        // Tattoy's `hello` is always JSON, it then tells us the encoding of everything else.
+        let Some(bytes) = read_bytes(&mut reader, tattoy_protocol::encoding::Encoding::Json)?
+        else {
+            return Ok(());
+        };
+        let first_message: tattoy_protocol::PluginInputMessages =
+            tattoy_protocol::encoding::decode(&bytes, tattoy_protocol::encoding::Encoding::Json)?;
+        let (encoding, settings) =
+            if let tattoy_protocol::PluginInputMessages::Hello {
+                encoding, settings, ..
+            } = first_message
+            {
+                (encoding, settings)
+            } else {
+                warn("Tattoy didn't start with a `hello` message");
+                (
+                    tattoy_protocol::encoding::Encoding::Json,
+                    tattoy_protocol::Settings::new(),
+                )
+            };
+        output.set_encoding(encoding);
+        self.plugin.configure(&settings)?;
+
+        let mut capabilities = self.plugin.capabilities();
+        capabilities
+            .protocol_version
+            .get_or_insert(tattoy_protocol::PROTOCOL_VERSION);
+        output.send(&tattoy_protocol::PluginOutputMessages::Capabilities(
+            capabilities,
+        ))?;
+
+        #[cfg(not(target_family = "wasm"))]
+        {
+            let (messages_tx, messages_rx) = std::sync::mpsc::channel();
+            std::thread::spawn(move || read_messages(reader, encoding, &messages_tx));
+            self.event_loop(&messages_rx, &mut output)
+        }
+
+        // WebAssembly plugins can't start threads, so messages are handled as soon as they're
+        // read and there are no ticks.
+        #[cfg(target_family = "wasm")]
+        {
+            while let Some(bytes) = read_bytes(&mut reader, encoding)? {
+                match tattoy_protocol::encoding::decode(&bytes, encoding) {
+                    Ok(message) => self.dispatch(&message, &mut output)?,
+                    Err(error) => warn(&format!("Skipping unknown message from Tattoy: {error}")),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Wait for messages from Tattoy and for ticks.
+    #[cfg(not(target_family = "wasm"))]
+    fn event_loop(
+        &mut self,
+        messages_rx: &std::sync::mpsc::Receiver<tattoy_protocol::PluginInputMessages>,
+        output: &mut crate::output::Output,
+    ) -> crate::Result<()> {
+        let tick_interval = self
+            .plugin
+            .tick_rate()
+            .and_then(|rate| std::time::Duration::from_secs(1).checked_div(rate));
+        let mut next_tick = std::time::Instant::now() + tick_interval.unwrap_or_default();
+
+        loop {
+            let received = match tick_interval {
+                Some(_) => messages_rx
+                    .recv_timeout(next_tick.saturating_duration_since(std::time::Instant::now())),
+                None => messages_rx.recv().map_err(|std::sync::mpsc::RecvError| {
+                    std::sync::mpsc::RecvTimeoutError::Disconnected
+                }),
+            };
+
+            match received {
+                Ok(message) => self.dispatch(&message, output)?,
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                    self.plugin.on_tick(&self.terminal, output)?;
+                    if let Some(interval) = tick_interval {
+                        // Don't try to catch up on missed ticks.
+                        next_tick = (next_tick + interval).max(std::time::Instant::now());
+                    }
+                }
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Update our copy of the terminal and call the relevant plugin callback.
+    fn dispatch(
+        &mut self,
+        message: &tattoy_protocol::PluginInputMessages,
+        output: &mut crate::output::Output,
+    ) -> crate::Result<()> {
+        match self.terminal.update(message) {
+            crate::terminal::Change::Screen => self.plugin.on_pty_update(&self.terminal, output),
+            crate::terminal::Change::Resize => self.plugin.on_resize(&self.terminal, output),
+            crate::terminal::Change::Other => {
+                self.plugin.on_message(message, &self.terminal, output)
+            }
+        }
+    }
+}
+
+/// Read messages from Tattoy until the reader ends. Messages that can't be parsed, most likely
+/// because they're from a newer version of Tattoy, are skipped.
+#[cfg(not(target_family = "wasm"))]
+fn read_messages<Reader: std::io::BufRead>(
+    mut reader: Reader,
+    encoding: tattoy_protocol::encoding::Encoding,
+    messages_tx: &std::sync::mpsc::Sender<tattoy_protocol::PluginInputMessages>,
+) {
+    loop {
+        let bytes = match read_bytes(&mut reader, encoding) {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => break,
+            Err(error) => {
+                warn(&format!("Couldn't read message from Tattoy: {error}"));
+                break;
+            }
+        };
+
+        match tattoy_protocol::encoding::decode(&bytes, encoding) {
+            Ok(message) => {
+                if messages_tx.send(message).is_err() {
+                    break;
+                }
+            }
+            Err(error) => warn(&format!("Skipping unknown message from Tattoy: {error}")),
+        }
+    }
+}
+
+/// Read the bytes of a single message. Returns `None` once the reader has ended.
+fn read_bytes<Reader: std::io::BufRead>(
+    reader: &mut Reader,
+    encoding: tattoy_protocol::encoding::Encoding,
+) -> std::io::Result<Option<Vec<u8>>> {
+    if encoding == tattoy_protocol::encoding::Encoding::MessagePack {
+        return tattoy_protocol::encoding::read_frame(reader);
+    }
+
+    loop {
+        let mut line = Vec::new();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        if !line.trim_ascii().is_empty() {
+            return Ok(Some(line));
+        }
+    }
+}
+
+/// Tattoy shows the user a plugin's STDERR when the plugin exits.
+#[expect(
+    clippy::print_stderr,
+    reason = "STDOUT is reserved for messages to Tattoy"
+)]
+fn warn(message: &str) {
+    eprintln!("{message}");
+}
+
+#[cfg(test)]
+#[expect(clippy::indexing_slicing, reason = "Tests aren't so strict")]
+mod test {
+    use super::*;
+
+    /// A writer that the test can still read from after it has been given to the runner.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0
+                .lock()
+                .map_err(|error| std::io::Error::other(error.to_string()))?
+                .extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Writes the first character of the terminal to the top-left of its canvas.
+    struct Echo;
+
+    impl crate::Plugin for Echo {
+        fn capabilities(&self) -> tattoy_protocol::Capabilities {
+            tattoy_protocol::Capabilities::builder()
+                .name("echo".to_owned())
+                .build()
+        }
+
+        fn on_pty_update(
+            &mut self,
+            terminal: &crate::terminal::Terminal,
+            output: &mut crate::output::Output,
+        ) -> crate::Result<()> {
+            let mut canvas = crate::canvas::Canvas::new(terminal.size.0, terminal.size.1);
+            let character = terminal.cells.first().unwrap().character;
+            canvas.set_text(0, 0, &character.to_string(), None, None);
+            output.send_canvas(&canvas)
+        }
+    }
+
+    fn hello(encoding: tattoy_protocol::encoding::Encoding) -> Vec<u8> {
+        tattoy_protocol::encoding::encode(
+            &tattoy_protocol::PluginInputMessages::Hello {
+                protocol_version: tattoy_protocol::PROTOCOL_VERSION,
+                tattoy_version: "0.1.0".to_owned(),
+                encoding,
+                settings: tattoy_protocol::Settings::new(),
+            },
+            tattoy_protocol::encoding::Encoding::Json,
+        )
+        .unwrap()
+    }
+
+    fn pty_update() -> tattoy_protocol::PluginInputMessages {
+        tattoy_protocol::PluginInputMessages::PTYUpdate {
+            sequence: 1,
+            size: (2, 1),
+            cells: vec![tattoy_protocol::Cell::builder()
+                .character('x')
+                .coordinates((1, 0))
+                .build()],
+            cursor: (0, 0),
+            cursor_shape: tattoy_protocol::CursorShape::Default,
+            is_cursor_visible: true,
+            is_alternate_screen: false,
+            title: None,
+            cwd: None,
+        }
+    }
+
+    fn run(input: Vec<u8>) -> Vec<u8> {
+        let buffer = SharedBuffer::default();
+        Runner::new(Echo)
+            .run(std::io::Cursor::new(input), Box::new(buffer.clone()))
+            .unwrap();
+        let output = buffer.0.lock().unwrap().clone();
+        output
+    }
+
+    fn assert_outputs(outputs: &[tattoy_protocol::PluginOutputMessages]) {
+        let tattoy_protocol::PluginOutputMessages::Capabilities(capabilities) =
+            outputs.first().unwrap()
+        else {
+            panic!("First output should be capabilities");
+        };
+        assert_eq!(capabilities.name, Some("echo".to_owned()));
+        assert_eq!(
+            capabilities.protocol_version,
+            Some(tattoy_protocol::PROTOCOL_VERSION)
+        );
+
+        let tattoy_protocol::PluginOutputMessages::OutputCells(cells) = &outputs[1] else {
+            panic!("Second output should be cells");
+        };
+        assert_eq!(cells.first().unwrap().character, 'x');
+        assert_eq!(cells.first().unwrap().coordinates, (0, 0));
+    }
+
+    #[test]
+    fn json_plugin() {
+        let mut input = hello(tattoy_protocol::encoding::Encoding::Json);
+        input.extend(b"{\"some_future_message\": {}}\n");
+        input.extend(
+            tattoy_protocol::encoding::encode(
+                &pty_update(),
+                tattoy_protocol::encoding::Encoding::Json,
+            )
+            .unwrap(),
+        );
+
+        let output = run(input);
+        let outputs: Vec<tattoy_protocol::PluginOutputMessages> =
+            serde_json::Deserializer::from_slice(&output)
+                .into_iter()
+                .map(Result::unwrap)
+                .collect();
+        assert_outputs(&outputs);
+    }
+
+    #[test]
+    fn message_pack_plugin() {
+        let mut input = hello(tattoy_protocol::encoding::Encoding::MessagePack);
+        input.extend(
+            tattoy_protocol::encoding::encode(
+                &pty_update(),
+                tattoy_protocol::encoding::Encoding::MessagePack,
+            )
+            .unwrap(),
+        );
+
+        let output = run(input);
+        let mut reader = output.as_slice();
+        let mut outputs = Vec::new();
+        while let Some(frame) = tattoy_protocol::encoding::read_frame(&mut reader).unwrap() {
+            outputs.push(
+                tattoy_protocol::encoding::decode(
+                    &frame,
+                    tattoy_protocol::encoding::Encoding::MessagePack,
+                )
+                .unwrap(),
+            );
+        }
+        assert_outputs(&outputs);
+    }
+}
@@ -0,0 +1,197 @@
+//! The plugin's copy of the state of the user's terminal.
+
+/// The true colours of the user's terminal palette.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Palette {
+    /// The 256 indexed colours of the palette, in index order.
+    pub colours: Vec<tattoy_protocol::Colour>,
+    /// The terminal's default foreground colour.
+    pub foreground: tattoy_protocol::Colour,
+    /// The terminal's default background colour.
+    pub background: tattoy_protocol::Colour,
+}
+
+/// The state of the user's terminal, as most recently sent by Tattoy.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Terminal {
+    /// The size of the terminal in columns and rows.
+    pub size: (u16, u16),
+    /// All the non-blank cells on the terminal's screen. Not in any particular order.
+    pub cells: Vec<tattoy_protocol::Cell>,
+    /// The position of the cursor.
+    pub cursor: (u16, u16),
+    /// The shape of the cursor.
+    pub cursor_shape: tattoy_protocol::CursorShape,
+    /// Whether the cursor is visible.
+    pub is_cursor_visible: bool,
+    /// Whether a full-screen application, like an editor, is running.
+    pub is_alternate_screen: bool,
+    /// The terminal's title, if one has been set.
+    pub title: Option<String>,
+    /// The current working directory, if the shell has reported it.
+    pub cwd: Option<String>,
+    /// The user's terminal palette. `None` until Tattoy has sent it.
+    pub palette: Option<Palette>,
+    /// The sequence number of the most recent PTY update.
+    pub sequence: u64,
+}
+
+/// How a message from Tattoy changed the terminal.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Change {
+    /// The contents of the screen changed.
+    Screen,
+    /// The terminal was resized.
+    Resize,
+    /// Nothing that the plugin needs a dedicated callback for.
+    Other,
+}
+
+impl Terminal {
+    /// Get the cell at the given coordinates, if it isn't blank.
+    #[inline]
+    #[must_use]
+    pub fn cell_at(&self, x: u32, y: u32) -> Option<&tattoy_protocol::Cell> {
+        self.cells.iter().find(|cell| cell.coordinates == (x, y))
+    }
+
+    /// Update the terminal with a message from Tattoy.
+    pub(crate) fn update(&mut self, message: &tattoy_protocol::PluginInputMessages) -> Change {
+        #[expect(
+            clippy::wildcard_enum_match_arm,
+            reason = "Every other message is handled by the plugin's `on_message()`"
+        )]
+        match message {
+            tattoy_protocol::PluginInputMessages::PTYUpdate {
+                sequence,
+                size,
+                cells,
+                cursor,
+                cursor_shape,
+                is_cursor_visible,
+                is_alternate_screen,
+                title,
+                cwd,
+            } => {
+                self.sequence = *sequence;
+                self.size = *size;
+                self.cells.clone_from(cells);
+                self.cursor = *cursor;
+                self.cursor_shape = *cursor_shape;
+                self.is_cursor_visible = *is_cursor_visible;
+                self.is_alternate_screen = *is_alternate_screen;
+                self.title.clone_from(title);
+                self.cwd.clone_from(cwd);
+                Change::Screen
+            }
+            tattoy_protocol::PluginInputMessages::PTYDiff {
+                sequence,
+                size,
+                changed_rows,
+                cells,
+                cursor,
+                cursor_shape,
+                is_cursor_visible,
+                is_alternate_screen,
+                title,
+                cwd,
+            } => {
+                self.sequence = *sequence;
+                self.size = *size;
+                self.cells.retain(|cell| {
+                    u16::try_from(cell.coordinates.1)
+                        .map_or(true, |row| !changed_rows.contains(&row))
+                });
+                self.cells.extend(cells.iter().cloned());
+                self.cursor = *cursor;
+                self.cursor_shape = *cursor_shape;
+                self.is_cursor_visible = *is_cursor_visible;
+                self.is_alternate_screen = *is_alternate_screen;
+                self.title.clone_from(title);
+                self.cwd.clone_from(cwd);
+                Change::Screen
+            }
+            tattoy_protocol::PluginInputMessages::TTYResize { width, height } => {
+                self.size = (*width, *height);
+                Change::Resize
+            }
+            tattoy_protocol::PluginInputMessages::Palette {
+                colours,
+                foreground,
+                background,
+            } => {
+                self.palette = Some(Palette {
+                    colours: colours.clone(),
+                    foreground: *foreground,
+                    background: *background,
+                });
+                Change::Other
+            }
+            _ => Change::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cell(character: char, coordinates: (u32, u32)) -> tattoy_protocol::Cell {
+        tattoy_protocol::Cell::builder()
+            .character(character)
+            .coordinates(coordinates)
+            .build()
+    }
+
+    #[test]
+    fn diffs_only_replace_changed_rows() {
+        let mut terminal = Terminal::default();
+        let update = tattoy_protocol::PluginInputMessages::PTYUpdate {
+            sequence: 1,
+            size: (2, 2),
+            cells: vec![cell('a', (0, 0)), cell('b', (0, 1))],
+            cursor: (0, 0),
+            cursor_shape: tattoy_protocol::CursorShape::Default,
+            is_cursor_visible: true,
+            is_alternate_screen: false,
+            title: None,
+            cwd: None,
+        };
+        assert_eq!(terminal.update(&update), Change::Screen);
+
+        let diff = tattoy_protocol::PluginInputMessages::PTYDiff {
+            sequence: 2,
+            size: (2, 2),
+            changed_rows: vec![1],
+            cells: vec![cell('c', (1, 1))],
+            cursor: (1, 1),
+            cursor_shape: tattoy_protocol::CursorShape::Default,
+            is_cursor_visible: true,
+            is_alternate_screen: false,
+            title: Some("title".to_owned()),
+            cwd: None,
+        };
+        assert_eq!(terminal.update(&diff), Change::Screen);
+
+        assert_eq!(terminal.sequence, 2);
+        assert_eq!(terminal.cells.len(), 2);
+        assert_eq!(terminal.cell_at(0, 0).unwrap().character, 'a');
+        assert!(terminal.cell_at(0, 1).is_none());
+        assert_eq!(terminal.cell_at(1, 1).unwrap().character, 'c');
+        assert_eq!(terminal.cursor, (1, 1));
+        assert_eq!(terminal.title, Some("title".to_owned()));
+    }
+
+    #[test]
+    fn resizing() {
+        let mut terminal = Terminal::default();
+        let resize = tattoy_protocol::PluginInputMessages::TTYResize {
+            width: 10,
+            height: 5,
+        };
+        assert_eq!(terminal.update(&resize), Change::Resize);
+        assert_eq!(terminal.size, (10, 5));
+    }
+}
@@ -4,45 +4,41 @@
 
 #![allow(clippy::restriction)]
 
-/// Entrypoint
-fn main() {
-    let lines = std::io::stdin().lines();
-
-    for line in lines {
-        let message: tattoy_protocol::PluginInputMessages =
-            serde_json::from_str(line.unwrap().as_str()).unwrap();
-
-        match message {
-            tattoy_protocol::PluginInputMessages::PTYUpdate {
-                size,
-                cells,
-                cursor: _,
-            } => {
-                if size.0 == 0 || size.1 == 0 {
-                    continue;
-                }
+/// The plugin. It doesn't need any state of its own.
+struct Inverter;
 
-                let tty_width = size.0;
-                let tty_height = size.1;
+impl tattoy_plugin_sdk::Plugin for Inverter {
+    fn capabilities(&self) -> tattoy_protocol::Capabilities {
+        tattoy_protocol::Capabilities::builder()
+            .name("inverter".to_owned())
+            .build()
+    }
 
-                let mut outgoing_cells = Vec::<tattoy_protocol::Cell>::new();
-                for incoming_cell in cells {
-                    let outgoing_cell = tattoy_protocol::Cell::builder()
-                        .character(incoming_cell.character)
-                        .coordinates((
-                            u32::from(tty_width) - incoming_cell.coordinates.0 - 1,
-                            u32::from(tty_height) - incoming_cell.coordinates.1 - 1,
-                        ))
-                        .maybe_bg(incoming_cell.bg)
-                        .maybe_fg(incoming_cell.fg)
-                        .build();
-                    outgoing_cells.push(outgoing_cell);
-                }
+    fn on_pty_update(
+        &mut self,
+        terminal: &tattoy_plugin_sdk::terminal::Terminal,
+        output: &mut tattoy_plugin_sdk::output::Output,
+    ) -> tattoy_plugin_sdk::Result<()> {
+        let (tty_width, tty_height) = terminal.size;
+        if tty_width == 0 || tty_height == 0 {
+            return Ok(());
+        }
 
-                let output = tattoy_protocol::PluginOutputMessages::OutputCells(outgoing_cells);
-                print!("{}", serde_json::to_string(&output).unwrap());
-            }
-            _ => todo!(),
+        let mut canvas = tattoy_plugin_sdk::canvas::Canvas::new(tty_width, tty_height);
+        for incoming_cell in &terminal.cells {
+            let mut outgoing_cell = incoming_cell.clone();
+            outgoing_cell.coordinates = (
+                u32::from(tty_width) - incoming_cell.coordinates.0 - 1,
+                u32::from(tty_height) - incoming_cell.coordinates.1 - 1,
+            );
+            canvas.set_cell(outgoing_cell);
         }
+
+        output.send_canvas(&canvas)
     }
 }
+
+/// Entrypoint
+fn main() -> tattoy_plugin_sdk::Result<()> {
+    tattoy_plugin_sdk::run(Inverter)
+}
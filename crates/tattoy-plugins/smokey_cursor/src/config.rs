@@ -1,7 +1,9 @@
-//! All the variables that can be configured for the simulation
+//! All the variables that can be configured for the simulation. They're read from the plugin's
+//! `[plugins.settings]` table in the user's Tattoy config.
 
 /// All the config for the simulation
-#[derive(Debug, Clone)]
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
 #[non_exhaustive]
 pub struct Config {
     /// The gravitational exceleration of the system in metres per second
@@ -18,7 +20,7 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             gravity: (0.0, -9.81),
-            initial_velocity: (0.0, 0.0),
+            initial_velocity: (0.01, -0.1),
             scale: 0.75,
             max_particles: 3000,
         }
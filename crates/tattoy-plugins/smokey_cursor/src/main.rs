@@ -9,12 +9,9 @@ pub mod simulation;
 use rand::Rng as _;
 use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _};
 
-#[tokio::main(flavor = "multi_thread")]
-async fn main() -> color_eyre::eyre::Result<()> {
-    let (messages_tx, messages_rx) = tokio::sync::mpsc::channel(16);
+fn main() -> color_eyre::eyre::Result<()> {
     setup_logging()?;
-    start_listener(messages_tx);
-    renderer::SmokeyCursor::start(messages_rx).await
+    tattoy_plugin_sdk::run(renderer::SmokeyCursor::new()).map_err(color_eyre::eyre::Report::msg)
 }
 
 /// Given a number, roll a dice of that size, and if it rolls a 1 then return `true`
@@ -24,29 +21,6 @@ pub fn is_random_trigger(chance: i64) -> bool {
     rng == 1i64
 }
 
-/// Start a dedicated thread for the input listener.
-fn start_listener(sender: tokio::sync::mpsc::Sender<tattoy_protocol::PluginInputMessages>) {
-    std::thread::spawn(move || {
-        let result = listen_for_tattoy_messages(&sender);
-        if let Err(error) = result {
-            tracing::error!("Error parsing JSON input: {error:?}");
-        }
-    });
-}
-
-/// Listen for JSON messages from Tattoy. Sent over STDIN.
-fn listen_for_tattoy_messages(
-    sender: &tokio::sync::mpsc::Sender<tattoy_protocol::PluginInputMessages>,
-) -> color_eyre::eyre::Result<()> {
-    tracing::debug!("Starting to listen on STDIN for messages from Tattoy");
-    for maybe_line in std::io::stdin().lines() {
-        let message: tattoy_protocol::PluginInputMessages =
-            serde_json::from_str(maybe_line?.as_str())?;
-        sender.blocking_send(message)?;
-    }
-    Ok(())
-}
-
 /// Setup logging to a file.
 fn setup_logging() -> color_eyre::eyre::Result<()> {
     let file = std::fs::OpenOptions::new()
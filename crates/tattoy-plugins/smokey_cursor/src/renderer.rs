@@ -1,136 +1,55 @@
-//! Manage the simulation and send and receive JSON from Tattoy.
+//! Manage the simulation and render it for Tattoy.
 
-use crate::simulation::Simulation;
-use color_eyre::eyre::Result;
-use std::{collections::VecDeque, io::Write as _};
-
-/// The number of microseconds in a second.
-pub const ONE_MICROSECOND: u64 = 1_000_000;
+use crate::{config::Config, simulation::Simulation};
+use std::collections::VecDeque;
 
 /// The target frame rate for renders sent to Tattoy.
-pub const TARGET_FRAME_RATE: u64 = 30;
-
-/// The current state of the Tattoy user's terminal.
-struct TTY {
-    /// The size of the user's terminal.
-    size: (u16, u16),
-    /// The current position of the cursor in the user's terminal.
-    cursor_position: (u16, u16),
-    /// The contens of the terminal's cells. Characters and colour values.
-    cells: Vec<tattoy_protocol::Cell>,
-}
+pub const TARGET_FRAME_RATE: u32 = 30;
 
 /// `SmokeyCursor`
 pub struct SmokeyCursor {
-    /// Details about the user's terminal.
-    tty: TTY,
     /// All the particles of the gas.
     simulation: Simulation,
     /// Timestamps of recent render ticks.
     durations: VecDeque<f64>,
-    /// The time at which the previous frame was rendererd.
-    last_frame_tick: tokio::time::Instant,
+    /// The user's settings for the simulation.
+    config: Config,
 }
 
 impl SmokeyCursor {
     /// Instatiate
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
-            tty: TTY {
-                size: (0, 0),
-                cursor_position: (0, 0),
-                cells: Vec::new(),
-            },
-            last_frame_tick: tokio::time::Instant::now(),
-            simulation: Simulation::new(0, 0),
+            simulation: Simulation::new(0, 0, Config::default()),
             durations: VecDeque::default(),
+            config: Config::default(),
         }
     }
 
     /// Initialise the simulation.
-    fn initialise(&mut self) {
-        self.simulation = Simulation::new(self.tty.size.0, self.tty.size.1 * 2);
+    fn initialise(&mut self, size: (u16, u16)) {
+        self.simulation = Simulation::new(size.0, size.1 * 2, self.config.clone());
 
         tracing::debug!("Simulation initialised.");
     }
 
-    /// Our main entrypoint.
-    pub(crate) async fn start(
-        mut messages: tokio::sync::mpsc::Receiver<tattoy_protocol::PluginInputMessages>,
-    ) -> Result<()> {
-        let mut smokey_cursor = Self::new();
-
-        #[expect(
-            clippy::integer_division_remainder_used,
-            reason = "This is caused by the `tokio::select!`"
-        )]
-        loop {
-            tokio::select! {
-                () = smokey_cursor.sleep_until_next_frame_tick() => {
-                    smokey_cursor.render()?;
-                },
-                Some(message) = messages.recv() => {
-                    smokey_cursor.handle_message(message);
-                }
-            }
-        }
-
-        #[expect(unreachable_code, reason = "We rely on Tattoy to shut us down")]
-        Ok(())
-    }
-
-    /// Sleep until the next frame render is due.
-    pub async fn sleep_until_next_frame_tick(&mut self) {
-        let target = crate::renderer::ONE_MICROSECOND.wrapping_div(TARGET_FRAME_RATE);
-        let target_frame_rate_micro = std::time::Duration::from_micros(target);
-        if let Some(wait) = target_frame_rate_micro.checked_sub(self.last_frame_tick.elapsed()) {
-            tokio::time::sleep(wait).await;
-        }
-        self.last_frame_tick = tokio::time::Instant::now();
-    }
-
-    /// Handle a protocol message from Tattoy.
-    fn handle_message(&mut self, message: tattoy_protocol::PluginInputMessages) {
-        match message {
-            tattoy_protocol::PluginInputMessages::PTYUpdate {
-                size,
-                cells,
-                cursor,
-            } => {
-                self.tty.size = size;
-                self.tty.cells = cells;
-                self.tty.cursor_position = cursor;
-            }
-            tattoy_protocol::PluginInputMessages::TTYResize { width, height } => {
-                self.tty.size = (width, height);
-                self.simulation.resize(width, height * 2);
-            }
-
-            #[expect(
-                clippy::unreachable,
-                reason = "
-                    Tattoy uses `#[non-exhaustive]` so have always be able to handle new
-                    message kinds without crashing
-                "
-            )]
-            _ => unreachable!(),
-        }
-    }
-
     /// Send a frame to Tattoy.
-    fn render(&mut self) -> Result<()> {
-        if self.tty.size.0 == 0 || self.tty.size.1 == 0 {
+    fn render(
+        &mut self,
+        terminal: &tattoy_plugin_sdk::terminal::Terminal,
+        output: &mut tattoy_plugin_sdk::output::Output,
+    ) -> tattoy_plugin_sdk::Result<()> {
+        if terminal.size.0 == 0 || terminal.size.1 == 0 {
             return Ok(());
         }
 
         if !self.simulation.is_ready() {
-            self.initialise();
+            self.initialise(terminal.size);
         }
 
         let start = std::time::Instant::now();
 
-        self.simulation
-            .tick(self.tty.cursor_position, &self.tty.cells);
+        self.simulation.tick(terminal.cursor, &terminal.cells);
 
         let mut pixels = Vec::<tattoy_protocol::Pixel>::new();
         #[expect(
@@ -153,20 +72,42 @@ impl SmokeyCursor {
             self.durations.pop_back();
         }
 
-        Self::send_output(pixels)?;
+        output.send(&tattoy_protocol::PluginOutputMessages::OutputPixels(pixels))
+    }
+}
+
+impl tattoy_plugin_sdk::Plugin for SmokeyCursor {
+    fn capabilities(&self) -> tattoy_protocol::Capabilities {
+        tattoy_protocol::Capabilities::builder()
+            .name("smokey_cursor".to_owned())
+            .frame_rate(TARGET_FRAME_RATE)
+            .build()
+    }
 
+    fn configure(&mut self, settings: &tattoy_protocol::Settings) -> tattoy_plugin_sdk::Result<()> {
+        self.config = serde_json::from_value(serde_json::Value::Object(settings.clone()))?;
         Ok(())
     }
 
-    /// Send pixel data to Tattoy for rendering.
-    fn send_output(pixels: Vec<tattoy_protocol::Pixel>) -> Result<()> {
-        let json =
-            serde_json::to_string(&tattoy_protocol::PluginOutputMessages::OutputPixels(pixels))?;
-        let mut stdout = std::io::stdout().lock();
-        let result = stdout.write_all(json.as_bytes());
-        if let Err(error) = result {
-            tracing::error!("Error sending json to Tattoy: {error:?}");
-        }
+    fn tick_rate(&self) -> Option<u32> {
+        Some(TARGET_FRAME_RATE)
+    }
+
+    fn on_resize(
+        &mut self,
+        terminal: &tattoy_plugin_sdk::terminal::Terminal,
+        _output: &mut tattoy_plugin_sdk::output::Output,
+    ) -> tattoy_plugin_sdk::Result<()> {
+        let (width, height) = terminal.size;
+        self.simulation.resize(width, height * 2);
         Ok(())
     }
+
+    fn on_tick(
+        &mut self,
+        terminal: &tattoy_plugin_sdk::terminal::Terminal,
+        output: &mut tattoy_plugin_sdk::output::Output,
+    ) -> tattoy_plugin_sdk::Result<()> {
+        self.render(terminal, output)
+    }
 }
@@ -38,11 +38,7 @@ pub struct Simulation {
 impl Simulation {
     /// Initialise a new simulation
     #[must_use]
-    pub fn new(width: u16, height: u16) -> Self {
-        let config = Config {
-            initial_velocity: Vec2::new(0.01, -0.1).into(),
-            ..Default::default()
-        };
+    pub fn new(width: u16, height: u16, config: Config) -> Self {
         let mut simulation = Self {
             width: 0.0,
             height: 0.0,
@@ -166,7 +162,7 @@ mod test {
     use super::*;
 
     fn make_sim() -> Simulation {
-        let mut sim = Simulation::new(100, 100);
+        let mut sim = Simulation::new(100, 100, Config::default());
         sim.config.gravity = Vec2::ZERO.into();
         sim.config.initial_velocity = Vec2::ZERO.into();
         sim.config.scale = 1.0 * crate::particle::PARTICLE_SIZE; // So we don't have to scale/unscale
@@ -183,7 +179,7 @@ mod test {
 
     #[test]
     fn basic() {
-        let mut sim = Simulation::new(100, 100);
+        let mut sim = Simulation::new(100, 100, Config::default());
         let pty = Vec::<tattoy_protocol::Cell>::new();
         for _ in 0usize..10 {
             sim.tick((50, 50), &pty);
@@ -0,0 +1,263 @@
+//! Encoding and decoding plugin protocol messages for sending over STDIN and STDOUT.
+
+/// The largest `MessagePack` message, in bytes, that will be read.
+///
+/// It's much bigger than even a full-screen update of a large terminal, but it stops a broken
+/// length prefix from making the reader allocate gigabytes of memory.
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// The encodings that plugin protocol messages can be sent with.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Encoding {
+    /// JSON. Messages from Tattoy are separated by newlines. Messages from plugins don't need any
+    /// separator.
+    #[default]
+    Json,
+    /// `MessagePack`. Every message is prefixed with its length in bytes, as a big-endian `u32`.
+    /// Much cheaper to encode and decode than JSON, so it's useful for plugins that send a lot of
+    /// data, like thousands of pixels per frame.
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+/// Encode a message, ready for writing to STDIN or STDOUT.
+///
+/// # Errors
+/// If the message can't be serialised.
+#[inline]
+#[expect(
+    clippy::big_endian_bytes,
+    reason = "Length prefixes are conventionally in network byte order"
+)]
+pub fn encode<Message: serde::Serialize>(
+    message: &Message,
+    encoding: Encoding,
+) -> std::io::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Json => {
+            let mut bytes = serde_json::to_vec(message)?;
+            bytes.push(b'\n');
+            Ok(bytes)
+        }
+        Encoding::MessagePack => {
+            let payload = rmp_serde::to_vec_named(message).map_err(std::io::Error::other)?;
+            let length = u32::try_from(payload.len()).map_err(std::io::Error::other)?;
+            let mut bytes = length.to_be_bytes().to_vec();
+            bytes.extend(payload);
+            Ok(bytes)
+        }
+    }
+}
+
+/// Decode a single message. For `MessagePack`, the bytes should not include the length prefix,
+/// see `read_frame()`.
+///
+/// # Errors
+/// If the bytes aren't a valid message.
+#[inline]
+pub fn decode<Message: serde::de::DeserializeOwned>(
+    bytes: &[u8],
+    encoding: Encoding,
+) -> std::io::Result<Message> {
+    match encoding {
+        Encoding::Json => Ok(serde_json::from_slice(bytes)?),
+        Encoding::MessagePack => rmp_serde::from_slice(bytes)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error)),
+    }
+}
+
+/// Read the bytes of a single length-prefixed `MessagePack` message. Returns `None` once the reader
+/// has reached its end.
+///
+/// # Errors
+/// If the reader fails, if it ends in the middle of a message, or if the message is bigger than
+/// `MAX_FRAME_SIZE`.
+#[inline]
+#[expect(
+    clippy::big_endian_bytes,
+    reason = "Length prefixes are conventionally in network byte order"
+)]
+pub fn read_frame<Reader: std::io::Read>(reader: &mut Reader) -> std::io::Result<Option<Vec<u8>>> {
+    let mut length_bytes = [0; 4];
+    let mut read_so_far = 0;
+    while let Some(remaining @ [_, ..]) = length_bytes.get_mut(read_so_far..) {
+        match reader.read(remaining) {
+            Ok(0) if read_so_far == 0 => return Ok(None),
+            Ok(0) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "Reader ended in the middle of a message's length prefix",
+                ))
+            }
+            Ok(count) => read_so_far += count,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(error) => return Err(error),
+        }
+    }
+
+    let length =
+        usize::try_from(u32::from_be_bytes(length_bytes)).map_err(std::io::Error::other)?;
+    if length > MAX_FRAME_SIZE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Message of {length} bytes is bigger than the maximum of {MAX_FRAME_SIZE}"),
+        ));
+    }
+    let mut payload = vec![0; length];
+    reader.read_exact(&mut payload)?;
+
+    Ok(Some(payload))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip<Message: serde::Serialize + serde::de::DeserializeOwned>(
+        message: &Message,
+        encoding: Encoding,
+    ) -> Message {
+        let bytes = encode(message, encoding).unwrap();
+        match encoding {
+            Encoding::Json => decode(&bytes, encoding).unwrap(),
+            Encoding::MessagePack => {
+                let frame = read_frame(&mut bytes.as_slice()).unwrap().unwrap();
+                decode(&frame, encoding).unwrap()
+            }
+        }
+    }
+
+    fn pixels() -> crate::PluginOutputMessages {
+        crate::PluginOutputMessages::OutputPixels(vec![
+            crate::Pixel::builder()
+                .coordinates((1, 2))
+                .color((0.1, 0.2, 0.3, 0.4))
+                .build(),
+            crate::Pixel::builder().coordinates((3, 4)).build(),
+        ])
+    }
+
+    fn image() -> crate::PluginOutputMessages {
+        crate::PluginOutputMessages::OutputImage {
+            origin: (1, 2),
+            width: 2,
+            height: 1,
+            pixels: vec![255, 0, 0, 255, 0, 255, 0, 128],
+        }
+    }
+
+    fn styled_text() -> crate::PluginOutputMessages {
+        crate::PluginOutputMessages::OutputText {
+            text: "foo".to_owned(),
+            coordinates: (1, 2),
+            bg: None,
+            fg: Some((0.1, 0.2, 0.3, 0.4)),
+            attributes: Some(crate::TextAttributes::builder().bold(true).build()),
+        }
+    }
+
+    fn pty_update() -> crate::PluginInputMessages {
+        crate::PluginInputMessages::PTYUpdate {
+            sequence: 1,
+            size: (1, 2),
+            cells: vec![crate::Cell::builder()
+                .character('f')
+                .coordinates((0, 1))
+                .build()],
+            cursor: (0, 0),
+            cursor_shape: crate::CursorShape::SteadyBar,
+            is_cursor_visible: true,
+            is_alternate_screen: false,
+            title: None,
+            cwd: None,
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        for message in [pixels(), image(), styled_text()] {
+            let decoded = round_trip(&message, Encoding::Json);
+            assert_eq!(format!("{decoded:?}"), format!("{message:?}"));
+        }
+
+        let message = pty_update();
+        let decoded = round_trip(&message, Encoding::Json);
+        assert_eq!(format!("{decoded:?}"), format!("{message:?}"));
+    }
+
+    #[test]
+    fn message_pack_round_trip() {
+        for message in [pixels(), image(), styled_text()] {
+            let decoded = round_trip(&message, Encoding::MessagePack);
+            assert_eq!(format!("{decoded:?}"), format!("{message:?}"));
+        }
+
+        let message = pty_update();
+        let decoded = round_trip(&message, Encoding::MessagePack);
+        assert_eq!(format!("{decoded:?}"), format!("{message:?}"));
+    }
+
+    #[test]
+    fn message_pack_partial_capabilities() {
+        #[derive(serde::Serialize)]
+        struct PartialCapabilities {
+            name: String,
+        }
+
+        let mut map = std::collections::BTreeMap::new();
+        map.insert(
+            "capabilities",
+            PartialCapabilities {
+                name: "foo".to_owned(),
+            },
+        );
+        let bytes = rmp_serde::to_vec_named(&map).unwrap();
+
+        let decoded: crate::PluginOutputMessages = decode(&bytes, Encoding::MessagePack).unwrap();
+        let crate::PluginOutputMessages::Capabilities(capabilities) = decoded else {
+            panic!("Wrong message type: {decoded:?}");
+        };
+        assert_eq!(capabilities.name, Some("foo".to_owned()));
+        assert!(capabilities.input.is_empty());
+    }
+
+    #[test]
+    fn reading_consecutive_frames() {
+        let mut bytes = encode(&pixels(), Encoding::MessagePack).unwrap();
+        bytes.extend(encode(&styled_text(), Encoding::MessagePack).unwrap());
+        let mut reader = bytes.as_slice();
+
+        assert!(read_frame(&mut reader).unwrap().is_some());
+        assert!(read_frame(&mut reader).unwrap().is_some());
+        assert!(read_frame(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_length_prefixes_are_rejected() {
+        let bytes = [0, 0];
+
+        let error = read_frame(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn truncated_messages_are_rejected() {
+        let mut bytes = encode(&pixels(), Encoding::MessagePack).unwrap();
+        bytes.pop();
+
+        let error = read_frame(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        // A length prefix of `u32::MAX`.
+        let mut bytes = vec![0xFF; 4];
+        bytes.extend([0; 16]);
+
+        let error = read_frame(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
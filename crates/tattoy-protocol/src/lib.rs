@@ -2,15 +2,28 @@
 
 #![expect(clippy::pub_use, reason = "This seems to come from the `bon` crate")]
 
+pub mod encoding;
+
+/// The version of the plugin protocol.
+///
+/// It is sent to plugins in the initial `Hello` message and plugins should send back the version
+/// they were built against in their `Capabilities` message. It is only incremented for changes
+/// that break existing plugins.
+pub const PROTOCOL_VERSION: u32 = 1;
+
 /// An RGBA colour.
 pub type Colour = (f32, f32, f32, f32);
 
+/// Free-form settings for a plugin, from the `[plugins.settings]` table of the user's Tattoy
+/// config.
+pub type Settings = serde_json::Map<String, serde_json::Value>;
+
 /// A cell represents a single character in the terminal.
 ///
 /// It can be sent from Tattoy to communicate the contents of the user's terminal.
 /// And it can also be sent from a plugin to communicate the contents to be composited
 /// in a Tattoy layer.
-#[derive(serde::Serialize, serde::Deserialize, bon::Builder, Clone, Copy, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, bon::Builder, Clone, Debug)]
 #[non_exhaustive]
 pub struct Cell {
     /// The cell's character.
@@ -23,6 +36,37 @@ pub struct Cell {
     /// An optional colour for the cell's foreground. If `None` (or `null` in the case of JSON) is
     /// used then the terminal's default foreground colour will be used.
     pub fg: Option<Colour>,
+    /// Optional styling for the cell's character, like bold or italic.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<TextAttributes>,
+}
+
+/// Styling for text, beyond its colours.
+#[derive(
+    serde::Serialize, serde::Deserialize, bon::Builder, Clone, Debug, Default, PartialEq, Eq,
+)]
+#[serde(default)]
+#[non_exhaustive]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "Each attribute is independently either on or off"
+)]
+pub struct TextAttributes {
+    /// Bold text.
+    #[builder(default)]
+    pub bold: bool,
+    /// Italic text.
+    #[builder(default)]
+    pub italic: bool,
+    /// Underlined text.
+    #[builder(default)]
+    pub underline: bool,
+    /// Text with a line through it.
+    #[builder(default)]
+    pub strikethrough: bool,
+    /// A URL that the text links to. Uses the OSC 8 hyperlink escape sequence, so the end user's
+    /// terminal must support it for the link to be clickable.
+    pub hyperlink: Option<String>,
 }
 
 /// Output from the plugin that renders pixels in the terminal.
@@ -38,20 +82,252 @@ pub struct Pixel {
     pub color: Option<Colour>,
 }
 
+/// The keyboard modifiers that were held down during an input event.
+#[derive(
+    serde::Serialize, serde::Deserialize, bon::Builder, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+#[non_exhaustive]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "Each modifier key is independently either held down or not"
+)]
+pub struct Modifiers {
+    /// The shift key.
+    #[builder(default)]
+    pub shift: bool,
+    /// The alt key, sometimes known as the option key.
+    #[builder(default)]
+    pub alt: bool,
+    /// The control key.
+    #[builder(default)]
+    pub ctrl: bool,
+    /// The super key, sometimes known as the command or Windows key.
+    #[serde(rename = "super")]
+    #[builder(default)]
+    pub super_key: bool,
+}
+
+/// The mouse buttons, including the scroll wheel directions.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum MouseButton {
+    /// The left mouse button.
+    Left,
+    /// The right mouse button.
+    Right,
+    /// The middle mouse button.
+    Middle,
+    /// The scroll wheel moving up.
+    WheelUp,
+    /// The scroll wheel moving down.
+    WheelDown,
+    /// The scroll wheel moving left.
+    WheelLeft,
+    /// The scroll wheel moving right.
+    WheelRight,
+}
+
+/// The kinds of user input that a plugin can choose to receive. Plugins don't receive any input by
+/// default.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum InputKind {
+    /// Key presses.
+    Key,
+    /// Mouse clicks, movement and scrolling.
+    Mouse,
+    /// Pasted text.
+    Paste,
+}
+
+/// A key chord, using the same syntax as Tattoy's keybinding config.
+#[derive(serde::Serialize, serde::Deserialize, bon::Builder, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct KeyChord {
+    /// The modifier keys, eg "CTRL", "ALT" or "CTRL|SHIFT".
+    pub mods: Option<String>,
+    /// The actual key, eg "p" or `PageUp`.
+    pub key: String,
+}
+
+/// The urgency of a notification sent by a plugin.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum NotificationLevel {
+    /// Something has gone wrong.
+    Error,
+    /// Something might go wrong.
+    Warn,
+    /// Just letting the user know about something.
+    #[default]
+    Info,
+    /// Details that are only useful when debugging.
+    Debug,
+    /// Even more details, for when debugging isn't enough.
+    Trace,
+}
+
+/// Input that a plugin sends to the user's shell, as if the user had typed it.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ShellInput {
+    /// Text, like a command or a snippet.
+    Text(String),
+    /// Raw bytes, for things like control characters or escape sequences.
+    Bytes(#[serde(with = "serde_bytes")] Vec<u8>),
+}
+
+/// The shape of the terminal's cursor.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum CursorShape {
+    /// Whatever shape the end user's terminal uses by default.
+    #[default]
+    Default,
+    /// A blinking block.
+    BlinkingBlock,
+    /// A block that doesn't blink.
+    SteadyBlock,
+    /// A blinking underline.
+    BlinkingUnderline,
+    /// An underline that doesn't blink.
+    SteadyUnderline,
+    /// A blinking vertical bar.
+    BlinkingBar,
+    /// A vertical bar that doesn't blink.
+    SteadyBar,
+}
+
+/// What the plugin is capable of and what it wants from Tattoy. Sent by the plugin when it starts.
+#[derive(serde::Serialize, serde::Deserialize, bon::Builder, Clone, Debug, Default)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// The version of the plugin protocol that the plugin was built against. Normally this should
+    /// just be `tattoy_protocol::PROTOCOL_VERSION`.
+    pub protocol_version: Option<u32>,
+    /// The plugin's own name for itself.
+    pub name: Option<String>,
+    /// The kinds of user input that the plugin wants to receive. The user's config takes
+    /// precedence.
+    #[builder(default)]
+    pub input: Vec<InputKind>,
+    /// The maximum rate, in frames per second, at which the plugin wants to receive PTY updates.
+    pub frame_rate: Option<u32>,
+    /// The key chords that the plugin wants to own. Matching key presses are only sent to this
+    /// plugin and are not forwarded to the user's shell. Tattoy's own keybindings always take
+    /// precedence.
+    #[builder(default)]
+    pub keybindings: Vec<KeyChord>,
+    /// Whether the plugin wants to receive the terminal's scrollback, see
+    /// `PluginInputMessages::ScrollbackUpdate`.
+    #[builder(default)]
+    pub scrollback: bool,
+    /// Whether the plugin wants to receive only the parts of the PTY screen that have changed,
+    /// see `PluginInputMessages::PTYDiff`.
+    #[builder(default)]
+    pub pty_diffs: bool,
+}
+
 /// The various kinds of messages that Tattoy can send to the plugin.
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 #[serde(rename_all = "snake_case")]
 #[non_exhaustive]
 pub enum PluginInputMessages {
+    /// The first message that Tattoy sends to the plugin. The plugin should reply with a
+    /// `Capabilities` message.
+    ///
+    /// It is always sent as JSON, whatever encoding the plugin is configured to use, so that any
+    /// plugin can read it.
+    Hello {
+        /// The version of the plugin protocol that Tattoy speaks.
+        protocol_version: u32,
+        /// The version of Tattoy itself.
+        tattoy_version: String,
+        /// The encoding of all the subsequent messages, in both directions. Including the
+        /// plugin's reply to this message.
+        encoding: encoding::Encoding,
+        /// The plugin's settings from the user's Tattoy config. Empty if there aren't any.
+        #[serde(default)]
+        settings: Settings,
+    },
     /// The current contents of the PTY screen. It does not contain any of the scrollback.
     #[serde(rename = "pty_update")]
     PTYUpdate {
+        /// Increments with every `PTYUpdate` and `PTYDiff`. So plugins can detect whether they
+        /// have missed an update.
+        sequence: u64,
         /// The size of terminal in colums and rows.
         size: (u16, u16),
         /// All the cell data for the current terminal. Blank cells are not included.
         cells: Vec<Cell>,
         /// The current position of the cursor.
         cursor: (u16, u16),
+        /// The current shape of the cursor.
+        cursor_shape: CursorShape,
+        /// Whether the cursor is currently visible.
+        is_cursor_visible: bool,
+        /// Whether the terminal is in the alternate screen. This is normally the case when a
+        /// full-screen application, like an editor, is running.
+        is_alternate_screen: bool,
+        /// The terminal's title, if one has been set.
+        title: Option<String>,
+        /// The current working directory, if the shell has reported it with the OSC 7 escape
+        /// sequence.
+        cwd: Option<String>,
+    },
+    /// Only the rows of the PTY screen that have changed since the previous `PTYUpdate` or
+    /// `PTYDiff`. Only sent to plugins that have opted in to PTY diffs. A full `PTYUpdate` is
+    /// still sent first, whenever the terminal resizes and periodically, so that plugins can
+    /// recover from any missed diffs.
+    #[serde(rename = "pty_diff")]
+    PTYDiff {
+        /// Increments with every `PTYUpdate` and `PTYDiff`. So plugins can detect whether they
+        /// have missed an update.
+        sequence: u64,
+        /// The size of terminal in colums and rows.
+        size: (u16, u16),
+        /// The rows that have changed. All the cells in these rows should be replaced with the
+        /// cells in `cells`.
+        changed_rows: Vec<u16>,
+        /// The new cell data for the changed rows. Blank cells are not included.
+        cells: Vec<Cell>,
+        /// The current position of the cursor.
+        cursor: (u16, u16),
+        /// The current shape of the cursor.
+        cursor_shape: CursorShape,
+        /// Whether the cursor is currently visible.
+        is_cursor_visible: bool,
+        /// Whether the terminal is in the alternate screen.
+        is_alternate_screen: bool,
+        /// The terminal's title, if one has been set.
+        title: Option<String>,
+        /// The current working directory, if the shell has reported it with the OSC 7 escape
+        /// sequence.
+        cwd: Option<String>,
+    },
+    /// The scrollback, the entire history of the terminal's output, including what is currently
+    /// on the screen. Only sent to plugins that have opted in to receiving the scrollback.
+    ///
+    /// The first update contains the entire scrollback. Subsequent updates only contain the lines
+    /// that have changed or been added since the previous update.
+    ScrollbackUpdate {
+        /// The index of the first line in `lines`. The plugin should replace all of its lines from
+        /// this index onwards with `lines`. `0` means that `lines` is the entire scrollback.
+        start: u32,
+        /// The total number of lines in the scrollback after this update.
+        height: u32,
+        /// The lines of the scrollback, oldest first. Each line is its non-blank cells. The
+        /// y-coordinate of a cell is its line's index in the entire scrollback.
+        lines: Vec<Vec<Cell>>,
+        /// How many lines the user has scrolled up from the bottom of the scrollback. `0` means
+        /// that the user isn't scrolling.
+        position: u32,
     },
     /// Sent whenever the terminal resizes.
     #[serde(rename = "tty_resize")]
@@ -61,6 +337,41 @@ pub enum PluginInputMessages {
         /// The number of rows in the new terminal size.
         height: u16,
     },
+    /// The true colours of the user's terminal palette. Sent when the plugin starts and whenever
+    /// the user's palette or config changes, so that plugins can match the user's theme.
+    Palette {
+        /// The 256 indexed colours of the palette, in index order.
+        colours: Vec<Colour>,
+        /// The terminal's default foreground colour.
+        foreground: Colour,
+        /// The terminal's default background colour.
+        background: Colour,
+    },
+    /// A key press from the end user. Only sent to plugins that have opted in to receiving key
+    /// input.
+    KeyInput {
+        /// The key that was pressed. Single characters are sent as-is, eg "a" or "A". Other keys
+        /// use the same names as Tattoy's keybinding config, eg `UpArrow`, `Escape`, `Enter`.
+        key: String,
+        /// The modifier keys held down during the key press.
+        modifiers: Modifiers,
+    },
+    /// A mouse event from the end user. Only sent to plugins that have opted in to receiving mouse
+    /// input.
+    MouseInput {
+        /// The column and row of the mouse. [0, 0] is in the top-left.
+        coordinates: (u16, u16),
+        /// The buttons that are pressed. Empty when the mouse has merely moved.
+        buttons: Vec<MouseButton>,
+        /// The modifier keys held down during the mouse event.
+        modifiers: Modifiers,
+    },
+    /// Text pasted by the end user. Only sent to plugins that have opted in to receiving paste
+    /// input.
+    PasteInput {
+        /// The pasted text.
+        text: String,
+    },
 }
 
 /// All the message kinds that the plugin can send to Tattoy.
@@ -78,6 +389,9 @@ pub enum PluginOutputMessages {
         bg: Option<Colour>,
         /// An optional colour for the text's foreground.
         fg: Option<Colour>,
+        /// Optional styling for the text, like bold or italic.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attributes: Option<TextAttributes>,
     },
 
     /// Output an arbitrary amount of cells to the terminal. It does not need to include blank
@@ -86,6 +400,44 @@ pub enum PluginOutputMessages {
 
     /// Output from the plugin that renders pixels in the terminal.
     OutputPixels(Vec<Pixel>),
+
+    /// Output a dense image of pixels to the terminal. Much cheaper than `OutputPixels` for
+    /// plugins that render every pixel of a region, like a background image or video.
+    OutputImage {
+        /// The pixel coordinates of the image's top-left corner. Like `Pixel`, the y-axis is
+        /// twice as long as the number of rows in the terminal.
+        origin: (u32, u32),
+        /// The width of the image in pixels.
+        width: u32,
+        /// The height of the image in pixels.
+        height: u32,
+        /// The RGBA values of every pixel, row by row, starting from the top-left. So it must
+        /// contain exactly `width * height * 4` bytes. Pixels with an alpha of `0` are not
+        /// rendered.
+        #[serde(with = "serde_bytes")]
+        pixels: Vec<u8>,
+    },
+
+    /// Tell Tattoy what the plugin is capable of and what it wants. Should be sent when the
+    /// plugin starts.
+    Capabilities(Capabilities),
+
+    /// Send input to the user's shell, as if the user had typed it. The user has to explicitly
+    /// allow this in the plugin's config, otherwise it is ignored.
+    SendInput(ShellInput),
+
+    /// Show a notification to the user. Notifications are rate-limited, so any sent in quick
+    /// succession may be dropped.
+    Notification {
+        /// The notification's main text.
+        title: String,
+        /// Optional further details.
+        #[serde(default)]
+        body: Option<String>,
+        /// The urgency of the notification.
+        #[serde(default)]
+        level: NotificationLevel,
+    },
 }
 
 #[expect(clippy::default_numeric_fallback, reason = "Tests aren't so strict")]
@@ -111,6 +463,7 @@ mod test {
             coordinates: (1, 2),
             bg: None,
             fg: Some((0.1, 0.2, 0.3, 0.4)),
+            attributes: None,
         };
 
         assert_eq!(
@@ -137,6 +490,7 @@ mod test {
             coordinates: (1, 2),
             bg: None,
             fg: Some((0.1, 0.2, 0.3, 0.4)),
+            attributes: None,
         }]);
 
         assert_eq!(
@@ -145,6 +499,148 @@ mod test {
         );
     }
 
+    #[test]
+    fn output_image() {
+        let expected = serde_json::json!(
+            {
+                "output_image": {
+                    "origin": [1, 2],
+                    "width": 1,
+                    "height": 2,
+                    "pixels": [255, 0, 0, 255, 0, 0, 255, 128],
+                }
+            }
+        );
+
+        let output = PluginOutputMessages::OutputImage {
+            origin: (1, 2),
+            width: 1,
+            height: 2,
+            pixels: vec![255, 0, 0, 255, 0, 0, 255, 128],
+        };
+
+        assert_eq!(
+            expected.to_string(),
+            serde_json::to_string(&output).unwrap()
+        );
+    }
+
+    #[test]
+    fn output_notification() {
+        let input = serde_json::json!(
+            {
+                "notification": {
+                    "title": "foo",
+                    "level": "warn",
+                }
+            }
+        );
+
+        let output: PluginOutputMessages = serde_json::from_value(input).unwrap();
+        let PluginOutputMessages::Notification { title, body, level } = output else {
+            panic!("Wrong message type: {output:?}");
+        };
+        assert_eq!(title, "foo");
+        assert_eq!(body, None);
+        assert_eq!(level, NotificationLevel::Warn);
+    }
+
+    #[test]
+    fn output_send_input_text() {
+        let expected = serde_json::json!(
+            {
+                "send_input": {
+                    "text": "ls\n",
+                }
+            }
+        );
+        let output = PluginOutputMessages::SendInput(ShellInput::Text("ls\n".to_owned()));
+        assert_eq!(
+            expected.to_string(),
+            serde_json::to_string(&output).unwrap()
+        );
+    }
+
+    #[test]
+    fn output_send_input_bytes() {
+        let input = serde_json::json!(
+            {
+                "send_input": {
+                    "bytes": [27, 91, 65],
+                }
+            }
+        );
+        let output: PluginOutputMessages = serde_json::from_value(input).unwrap();
+        let PluginOutputMessages::SendInput(ShellInput::Bytes(bytes)) = output else {
+            panic!("Wrong message type: {output:?}");
+        };
+        assert_eq!(bytes, vec![27, 91, 65]);
+    }
+
+    #[test]
+    fn output_text_with_attributes() {
+        let expected = serde_json::json!(
+            {
+                "output_text": {
+                    "text": "foo",
+                    "coordinates": [1, 2],
+                    "bg": null,
+                    "fg": null,
+                    "attributes": {
+                        "bold": true,
+                        "italic": false,
+                        "underline": true,
+                        "strikethrough": false,
+                        "hyperlink": "https://tattoy.sh",
+                    },
+                }
+            }
+        );
+
+        let output = PluginOutputMessages::OutputText {
+            text: "foo".to_owned(),
+            coordinates: (1, 2),
+            bg: None,
+            fg: None,
+            attributes: Some(
+                TextAttributes::builder()
+                    .bold(true)
+                    .underline(true)
+                    .hyperlink("https://tattoy.sh".to_owned())
+                    .build(),
+            ),
+        };
+
+        assert_eq!(
+            expected.to_string(),
+            serde_json::to_string(&output).unwrap()
+        );
+    }
+
+    #[test]
+    fn output_cells_with_partial_attributes() {
+        let json = r#"
+            {
+                "output_cells": [{
+                    "character": "f",
+                    "coordinates": [1, 2],
+                    "bg": null,
+                    "fg": null,
+                    "attributes": { "italic": true }
+                }]
+            }
+        "#;
+
+        let output: PluginOutputMessages = serde_json::from_str(json).unwrap();
+        let PluginOutputMessages::OutputCells(cells) = output else {
+            panic!("Wrong message type: {output:?}");
+        };
+        assert_eq!(
+            cells.first().unwrap().attributes,
+            Some(TextAttributes::builder().italic(true).build())
+        );
+    }
+
     #[test]
     fn output_pixels() {
         let expected = serde_json::json!(
@@ -167,11 +663,162 @@ mod test {
         );
     }
 
+    #[test]
+    fn output_capabilities() {
+        let expected = serde_json::json!(
+            {
+                "capabilities": {
+                    "protocol_version": 1,
+                    "name": "foo",
+                    "input": ["key"],
+                    "frame_rate": 30,
+                    "keybindings": [{
+                        "mods": "ALT",
+                        "key": "p",
+                    }],
+                    "scrollback": true,
+                    "pty_diffs": false,
+                }
+            }
+        );
+
+        let output = PluginOutputMessages::Capabilities(Capabilities {
+            protocol_version: Some(1),
+            name: Some("foo".to_owned()),
+            input: vec![InputKind::Key],
+            frame_rate: Some(30),
+            keybindings: vec![KeyChord {
+                mods: Some("ALT".to_owned()),
+                key: "p".to_owned(),
+            }],
+            scrollback: true,
+            pty_diffs: false,
+        });
+
+        assert_eq!(
+            expected.to_string(),
+            serde_json::to_string(&output).unwrap()
+        );
+    }
+
+    #[test]
+    fn output_capabilities_defaults() {
+        let capabilities: Capabilities = serde_json::from_value(serde_json::json!({})).unwrap();
+
+        assert_eq!(capabilities.protocol_version, None);
+        assert!(capabilities.input.is_empty());
+        assert!(capabilities.keybindings.is_empty());
+        assert!(!capabilities.scrollback);
+    }
+
+    #[test]
+    fn input_scrollback_update() {
+        let expected = serde_json::json!(
+            {
+                "scrollback_update": {
+                    "start": 0,
+                    "height": 2,
+                    "lines": [
+                        [{
+                            "character": "f",
+                            "coordinates": [0, 0],
+                            "bg": null,
+                            "fg": null,
+                        }],
+                        [],
+                    ],
+                    "position": 1,
+                }
+            }
+        );
+
+        let output = PluginInputMessages::ScrollbackUpdate {
+            start: 0,
+            height: 2,
+            lines: vec![
+                vec![Cell::builder().character('f').coordinates((0, 0)).build()],
+                vec![],
+            ],
+            position: 1,
+        };
+
+        assert_eq!(
+            expected.to_string(),
+            serde_json::to_string(&output).unwrap()
+        );
+    }
+
+    #[test]
+    fn input_palette() {
+        let expected = serde_json::json!(
+            {
+                "palette": {
+                    "colours": [[0.0, 0.0, 0.0, 1.0], [1.0, 0.0, 0.0, 1.0]],
+                    "foreground": [1.0, 1.0, 1.0, 1.0],
+                    "background": [0.0, 0.0, 0.0, 1.0],
+                }
+            }
+        );
+
+        let output = PluginInputMessages::Palette {
+            colours: vec![(0.0, 0.0, 0.0, 1.0), (1.0, 0.0, 0.0, 1.0)],
+            foreground: (1.0, 1.0, 1.0, 1.0),
+            background: (0.0, 0.0, 0.0, 1.0),
+        };
+
+        assert_eq!(
+            expected.to_string(),
+            serde_json::to_string(&output).unwrap()
+        );
+    }
+
+    #[test]
+    fn input_hello() {
+        let expected = serde_json::json!(
+            {
+                "hello": {
+                    "protocol_version": 1,
+                    "tattoy_version": "0.1.0",
+                    "encoding": "msgpack",
+                    "settings": {
+                        "scale": 0.5
+                    },
+                }
+            }
+        );
+
+        let mut settings = Settings::new();
+        settings.insert("scale".to_owned(), serde_json::json!(0.5));
+        let output = PluginInputMessages::Hello {
+            protocol_version: 1,
+            tattoy_version: "0.1.0".to_owned(),
+            encoding: encoding::Encoding::MessagePack,
+            settings,
+        };
+
+        assert_eq!(
+            expected.to_string(),
+            serde_json::to_string(&output).unwrap()
+        );
+    }
+
+    #[test]
+    fn input_hello_without_settings() {
+        let input =
+            r#"{"hello": {"protocol_version": 1, "tattoy_version": "0.1.0", "encoding": "json"}}"#;
+        let PluginInputMessages::Hello { settings, .. } = serde_json::from_str(input).unwrap()
+        else {
+            panic!("Should be a hello message");
+        };
+        assert!(settings.is_empty());
+    }
+
     #[test]
     fn input_pty_update() {
         let expected = serde_json::json!(
             {
                 "pty_update": {
+                    "sequence": 3,
                     "size": [1, 2],
                     "cells": [{
                         "character": "f",
@@ -180,19 +827,74 @@ mod test {
                         "fg": [0.1, 0.2, 0.3, 0.4],
                     }],
                     "cursor": [9, 10],
+                    "cursor_shape": "steady_bar",
+                    "is_cursor_visible": true,
+                    "is_alternate_screen": false,
+                    "title": "vim",
+                    "cwd": null,
                 }
             }
         );
 
         let output = PluginInputMessages::PTYUpdate {
+            sequence: 3,
             size: (1, 2),
             cells: vec![Cell {
                 character: 'f',
                 coordinates: (1, 2),
                 bg: None,
                 fg: Some((0.1, 0.2, 0.3, 0.4)),
+                attributes: None,
             }],
             cursor: (9, 10),
+            cursor_shape: CursorShape::SteadyBar,
+            is_cursor_visible: true,
+            is_alternate_screen: false,
+            title: Some("vim".to_owned()),
+            cwd: None,
+        };
+
+        assert_eq!(
+            expected.to_string(),
+            serde_json::to_string(&output).unwrap()
+        );
+    }
+
+    #[test]
+    fn input_pty_diff() {
+        let expected = serde_json::json!(
+            {
+                "pty_diff": {
+                    "sequence": 4,
+                    "size": [1, 2],
+                    "changed_rows": [0, 1],
+                    "cells": [{
+                        "character": "f",
+                        "coordinates": [0, 1],
+                        "bg": null,
+                        "fg": null,
+                    }],
+                    "cursor": [1, 1],
+                    "cursor_shape": "default",
+                    "is_cursor_visible": true,
+                    "is_alternate_screen": false,
+                    "title": null,
+                    "cwd": null,
+                }
+            }
+        );
+
+        let output = PluginInputMessages::PTYDiff {
+            sequence: 4,
+            size: (1, 2),
+            changed_rows: vec![0, 1],
+            cells: vec![Cell::builder().character('f').coordinates((0, 1)).build()],
+            cursor: (1, 1),
+            cursor_shape: CursorShape::Default,
+            is_cursor_visible: true,
+            is_alternate_screen: false,
+            title: None,
+            cwd: None,
         };
 
         assert_eq!(
@@ -222,4 +924,60 @@ mod test {
             serde_json::to_string(&output).unwrap()
         );
     }
+
+    #[test]
+    fn input_key_input() {
+        let expected = serde_json::json!(
+            {
+                "key_input": {
+                    "key": "a",
+                    "modifiers": {
+                        "shift": false,
+                        "alt": true,
+                        "ctrl": false,
+                        "super": false,
+                    },
+                }
+            }
+        );
+
+        let output = PluginInputMessages::KeyInput {
+            key: "a".to_owned(),
+            modifiers: Modifiers::builder().alt(true).build(),
+        };
+
+        assert_eq!(
+            expected.to_string(),
+            serde_json::to_string(&output).unwrap()
+        );
+    }
+
+    #[test]
+    fn input_mouse_input() {
+        let expected = serde_json::json!(
+            {
+                "mouse_input": {
+                    "coordinates": [1, 2],
+                    "buttons": ["left", "wheel_up"],
+                    "modifiers": {
+                        "shift": false,
+                        "alt": false,
+                        "ctrl": false,
+                        "super": false,
+                    },
+                }
+            }
+        );
+
+        let output = PluginInputMessages::MouseInput {
+            coordinates: (1, 2),
+            buttons: vec![MouseButton::Left, MouseButton::WheelUp],
+            modifiers: Modifiers::default(),
+        };
+
+        assert_eq!(
+            expected.to_string(),
+            serde_json::to_string(&output).unwrap()
+        );
+    }
 }
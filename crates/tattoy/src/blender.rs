@@ -16,6 +16,66 @@ pub(crate) enum Kind {
     Background,
 }
 
+/// How the colours of a layer are combined with the colours of the layers below it. The result is
+/// then mixed with the colours below according to the layer's opacity and alpha.
+#[derive(serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BlendMode {
+    /// The layer's colours simply replace the colours below.
+    #[default]
+    Normal,
+    /// Darkens the colours below. White leaves them unchanged.
+    Multiply,
+    /// Lightens the colours below. Black leaves them unchanged.
+    Screen,
+    /// Multiplies dark colours below and screens light ones, increasing contrast.
+    Overlay,
+    /// Adds the colours together, clamped to white. Good for glows.
+    Additive,
+    /// Keeps the darkest of each colour channel.
+    Darken,
+    /// Keeps the lightest of each colour channel.
+    Lighten,
+}
+
+impl BlendMode {
+    /// Combine a colour from a layer with the colour below it. The alpha of the layer's colour is
+    /// kept, so that it can still be used to mix the result with the colour below.
+    pub fn apply(
+        self,
+        below: termwiz::color::SrgbaTuple,
+        above: termwiz::color::SrgbaTuple,
+    ) -> termwiz::color::SrgbaTuple {
+        termwiz::color::SrgbaTuple(
+            self.apply_to_channel(below.0, above.0),
+            self.apply_to_channel(below.1, above.1),
+            self.apply_to_channel(below.2, above.2),
+            above.3,
+        )
+    }
+
+    /// Combine a single colour channel.
+    fn apply_to_channel(self, below: f32, above: f32) -> f32 {
+        match self {
+            Self::Normal => above,
+            Self::Multiply => below * above,
+            // 1 - (1 - below) * (1 - above)
+            Self::Screen => (1.0 - below).mul_add(above - 1.0, 1.0),
+            Self::Overlay => {
+                if below < 0.5 {
+                    2.0 * below * above
+                } else {
+                    // 1 - 2 * (1 - below) * (1 - above)
+                    (2.0 * (1.0 - below)).mul_add(above - 1.0, 1.0)
+                }
+            }
+            Self::Additive => (below + above).min(1.0),
+            Self::Darken => below.min(above),
+            Self::Lighten => below.max(above),
+        }
+    }
+}
+
 /// Just a convenience wrapper around Termwiz's `[Cell]`. Compositing cells is a bit tricky, so
 /// having a dedicated module hopefully makes things a bit simpler.
 pub(crate) struct Blender<'cell> {
@@ -25,6 +85,8 @@ pub(crate) struct Blender<'cell> {
     default_colour: termwiz::color::SrgbaTuple,
     /// The opacity of the cell above.
     cell_above_opacity: f32,
+    /// How the colours of the cell above are combined with this cell's colours.
+    blend_mode: BlendMode,
 }
 
 impl<'cell> Blender<'cell> {
@@ -33,11 +95,13 @@ impl<'cell> Blender<'cell> {
         cell: &'cell mut termwiz::cell::Cell,
         default_bg_colour: termwiz::color::SrgbaTuple,
         cell_above_opacity: f32,
+        blend_mode: BlendMode,
     ) -> Self {
         Self {
             cell,
             default_colour: default_bg_colour,
             cell_above_opacity,
+            blend_mode,
         }
     }
 
@@ -77,7 +141,7 @@ impl<'cell> Blender<'cell> {
         let colour = Self::extract_colour(this_colour_attribute).unwrap_or(self.default_colour);
 
         let blended_colour = colour.interpolate(
-            incoming_colour,
+            self.blend_mode.apply(colour, incoming_colour),
             f64::from(incoming_colour.3 * self.cell_above_opacity),
         );
         let attribute = Self::make_true_colour_attribute(blended_colour);
@@ -346,6 +410,40 @@ mod test {
         assert!(cell.attrs().reverse());
     }
 
+    #[tokio::test]
+    async fn compositing_preserves_styled_text() {
+        let mut renderer = make_renderer().await;
+        let mut tattoy_below = crate::surface::Surface::new("below".into(), 1, 1, 1, 1.0);
+        tattoy_below.add_text(0, 0, "a".into(), None, None);
+        renderer
+            .tattoys
+            .insert(tattoy_below.id.clone(), tattoy_below);
+
+        let mut tattoy_above = crate::surface::Surface::new("above".into(), 1, 1, 2, 1.0);
+        let attributes = tattoy_protocol::TextAttributes::builder()
+            .bold(true)
+            .hyperlink("https://tattoy.sh".to_owned())
+            .build();
+        tattoy_above.add_styled_text(
+            0,
+            0,
+            "x".into(),
+            Some((0.0, 0.0, 0.0, 0.5)),
+            None,
+            Some(&attributes),
+        );
+        renderer
+            .tattoys
+            .insert(tattoy_above.id.clone(), tattoy_above);
+
+        renderer.composite().await.unwrap();
+        let cell = &renderer.frame.screen_cells()[0][0];
+
+        assert_eq!(cell.str(), "x");
+        assert_eq!(cell.attrs().intensity(), termwiz::cell::Intensity::Bold);
+        assert_eq!(cell.attrs().hyperlink().unwrap().uri(), "https://tattoy.sh");
+    }
+
     #[tokio::test]
     async fn blending_text_with_default_bg_below() {
         let mut renderer = make_renderer().await;
@@ -403,6 +501,85 @@ mod test {
         );
     }
 
+    #[tokio::test]
+    async fn compositing_region_larger_than_frame_is_clipped() {
+        let mut renderer = make_renderer().await;
+        let mut badge = crate::surface::Surface::new("badge".into(), 2, 2, 1, 1.0);
+        badge.region = Some(crate::surface::Region {
+            anchor: crate::surface::Anchor::BottomRight,
+            offset: (0, 0),
+        });
+        badge.add_text(0, 0, "ab".into(), None, Some(crate::surface::WHITE));
+        renderer.tattoys.insert(badge.id.clone(), badge);
+
+        renderer.composite().await.unwrap();
+        let cell = &renderer.frame.screen_cells()[0][0];
+
+        assert_eq!(cell.str(), "a");
+    }
+
+    #[tokio::test]
+    async fn blending_with_multiply_blend_mode() {
+        let mut renderer = make_renderer().await;
+        let mut tattoy_below = crate::surface::Surface::new("below".into(), 1, 1, 1, 1.0);
+        tattoy_below.add_text(0, 0, " ".into(), Some((1.0, 0.5, 0.5, 1.0)), None);
+        renderer
+            .tattoys
+            .insert(tattoy_below.id.clone(), tattoy_below);
+
+        let mut tattoy_above = crate::surface::Surface::new("above".into(), 1, 1, 2, 0.5);
+        tattoy_above.blend_mode = crate::blender::BlendMode::Multiply;
+        tattoy_above.add_text(0, 0, " ".into(), Some((0.5, 0.5, 0.5, 1.0)), None);
+        renderer
+            .tattoys
+            .insert(tattoy_above.id.clone(), tattoy_above);
+
+        renderer.composite().await.unwrap();
+        let cell = &renderer.frame.screen_cells()[0][0];
+
+        assert_eq!(
+            cell.attrs().background(),
+            termwiz::color::ColorAttribute::TrueColorWithDefaultFallback(
+                termwiz::color::SrgbaTuple(0.75, 0.375, 0.375, 1.0)
+            )
+        );
+    }
+
+    #[test]
+    fn blend_modes() {
+        use crate::blender::BlendMode;
+
+        let below = termwiz::color::SrgbaTuple(0.25, 0.75, 0.5, 1.0);
+        let above = termwiz::color::SrgbaTuple(0.5, 0.5, 1.0, 0.5);
+        let apply = |mode: BlendMode| mode.apply(below, above);
+
+        assert_eq!(apply(BlendMode::Normal), above);
+        assert_eq!(
+            apply(BlendMode::Multiply),
+            termwiz::color::SrgbaTuple(0.125, 0.375, 0.5, 0.5)
+        );
+        assert_eq!(
+            apply(BlendMode::Screen),
+            termwiz::color::SrgbaTuple(0.625, 0.875, 1.0, 0.5)
+        );
+        assert_eq!(
+            apply(BlendMode::Overlay),
+            termwiz::color::SrgbaTuple(0.25, 0.75, 1.0, 0.5)
+        );
+        assert_eq!(
+            apply(BlendMode::Additive),
+            termwiz::color::SrgbaTuple(0.75, 1.0, 1.0, 0.5)
+        );
+        assert_eq!(
+            apply(BlendMode::Darken),
+            termwiz::color::SrgbaTuple(0.25, 0.5, 0.5, 0.5)
+        );
+        assert_eq!(
+            apply(BlendMode::Lighten),
+            termwiz::color::SrgbaTuple(0.5, 0.75, 1.0, 0.5)
+        );
+    }
+
     #[tokio::test]
     async fn upper_and_lower_pixels_in_same_cell_dont_blend() {
         let cell = blend_pixels(
@@ -0,0 +1,376 @@
+//! Colour grading of the final composited frame. As well as the basic saturation, brightness and
+//! hue adjustments, there's contrast, gamma, colour temperature and tint, and 3D LUTs (lookup
+//! tables) loaded from `.cube` files.
+
+use color_eyre::eyre::{bail, Result};
+
+use shadow_terminal::termwiz;
+
+/// How much a temperature or tint of 1.0 scales the affected colour channels.
+const TEMPERATURE_STRENGTH: f32 = 0.2;
+
+/// The largest LUT that we accept. Bigger LUTs are very rare and would use a lot of memory.
+const MAX_LUT_SIZE: usize = 256;
+
+/// A 3D lookup table, as used in film and photo editing, that maps every colour to another
+/// colour.
+#[derive(Debug, Clone)]
+pub(crate) struct Lut {
+    /// The number of entries along each of the red, green and blue axes.
+    size: usize,
+    /// The output colours. As in `.cube` files, red changes fastest, then green, then blue.
+    table: Vec<[f32; 3]>,
+    /// The input colour that maps to the first entry of each axis.
+    domain_min: [f32; 3],
+    /// The input colour that maps to the last entry of each axis.
+    domain_max: [f32; 3],
+}
+
+impl Lut {
+    /// Load a `.cube` LUT file.
+    pub fn load(path: &std::path::Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|error| {
+            color_eyre::eyre::eyre!("Couldn't read LUT file {}: {error}", path.display())
+        })?;
+        Self::parse(&contents)
+    }
+
+    /// Parse the contents of a `.cube` LUT file. Only 3D LUTs are supported. Keywords that don't
+    /// affect 3D LUTs, like the ones that some editors add, are ignored.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut size = None;
+        let mut domain_min = [0.0, 0.0, 0.0];
+        let mut domain_max = [1.0, 1.0, 1.0];
+        let mut table = Vec::new();
+
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let Some(keyword) = words.next() else {
+                continue;
+            };
+            match keyword {
+                "TITLE" => (),
+                "LUT_3D_SIZE" => {
+                    let parsed: usize = words.next().unwrap_or_default().parse()?;
+                    if !(2..=MAX_LUT_SIZE).contains(&parsed) {
+                        bail!("LUT size must be between 2 and {MAX_LUT_SIZE}, not {parsed}");
+                    }
+                    size = Some(parsed);
+                }
+                "LUT_1D_SIZE" => bail!("1D LUTs aren't supported, only 3D LUTs"),
+                "DOMAIN_MIN" => domain_min = Self::parse_triple(words)?,
+                "DOMAIN_MAX" => domain_max = Self::parse_triple(words)?,
+                // The same as a `DOMAIN_MIN` and `DOMAIN_MAX` with equal channels.
+                "LUT_3D_INPUT_RANGE" => {
+                    let (Some(min), Some(max)) = (words.next(), words.next()) else {
+                        bail!("LUT's `LUT_3D_INPUT_RANGE` needs a minimum and a maximum");
+                    };
+                    domain_min = [min.parse()?; 3];
+                    domain_max = [max.parse()?; 3];
+                }
+                _ if keyword.starts_with(|character: char| character.is_ascii_alphabetic()) => {
+                    tracing::debug!("Ignoring unsupported LUT keyword: {keyword}");
+                }
+                _ => table.push(Self::parse_triple(line.split_whitespace())?),
+            }
+        }
+
+        let Some(lut_size) = size else {
+            bail!("LUT is missing its `LUT_3D_SIZE`");
+        };
+        let expected_entries = lut_size.pow(3);
+        if table.len() != expected_entries {
+            bail!(
+                "LUT of size {lut_size} should have {expected_entries} entries, but has {}",
+                table.len()
+            );
+        }
+        if domain_min
+            .iter()
+            .zip(domain_max)
+            .any(|(min, max)| *min >= max)
+        {
+            bail!("LUT's `DOMAIN_MIN` must be less than its `DOMAIN_MAX`");
+        }
+
+        Ok(Self {
+            size: lut_size,
+            table,
+            domain_min,
+            domain_max,
+        })
+    }
+
+    /// Parse 3 numbers, like a LUT entry or a domain.
+    fn parse_triple<'line>(mut words: impl Iterator<Item = &'line str>) -> Result<[f32; 3]> {
+        let mut triple = [0.0; 3];
+        for value in &mut triple {
+            let Some(word) = words.next() else {
+                bail!("Expected 3 numbers in LUT");
+            };
+            *value = word.parse()?;
+        }
+        Ok(triple)
+    }
+
+    /// Map a colour through the LUT. Colours that fall between the LUT's entries are
+    /// interpolated from the 8 surrounding entries.
+    #[must_use]
+    pub fn apply(&self, colour: [f32; 3]) -> [f32; 3] {
+        // For each channel, the index of the entry below the colour and how far the colour is
+        // towards the next entry.
+        let mut corners = [(0, 0.0); 3];
+        for (((corner, value), min), max) in corners
+            .iter_mut()
+            .zip(colour)
+            .zip(self.domain_min)
+            .zip(self.domain_max)
+        {
+            let position = ((value - min) / (max - min)).clamp(0.0, 1.0) * self.max_index();
+            // The last entry doesn't have an entry after it, so interpolate to it from the one
+            // before.
+            let lower_position = position.floor().min(self.max_index() - 1.0);
+            *corner = (
+                Self::float_to_index(lower_position),
+                position - lower_position,
+            );
+        }
+
+        let [(red_index, red_fraction), (green_index, green_fraction), (blue_index, blue_fraction)] =
+            corners;
+        let mut result = [0.0; 3];
+        for (blue, blue_weight) in [
+            (blue_index, 1.0 - blue_fraction),
+            (blue_index + 1, blue_fraction),
+        ] {
+            for (green, green_weight) in [
+                (green_index, 1.0 - green_fraction),
+                (green_index + 1, green_fraction),
+            ] {
+                for (red, red_weight) in [
+                    (red_index, 1.0 - red_fraction),
+                    (red_index + 1, red_fraction),
+                ] {
+                    let weight = red_weight * green_weight * blue_weight;
+                    let entry = self
+                        .table
+                        .get(red + green * self.size + blue * self.size * self.size)
+                        .copied()
+                        .unwrap_or_default();
+                    for (output, value) in result.iter_mut().zip(entry) {
+                        *output = value.mul_add(weight, *output);
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    /// The index of the last entry along each axis, as a float.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_precision_loss,
+        reason = "LUT sizes are tiny"
+    )]
+    const fn max_index(&self) -> f32 {
+        (self.size - 1) as f32
+    }
+
+    /// Convert an already-floored, non-negative, float to an index.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation,
+        reason = "The float is always a small, positive, whole number"
+    )]
+    const fn float_to_index(float: f32) -> usize {
+        float as usize
+    }
+}
+
+/// Apply all of the user's colour grading to a single colour.
+#[must_use]
+pub(crate) fn grade(
+    colour: termwiz::color::SrgbaTuple,
+    config: &crate::config::main::Color,
+    maybe_lut: Option<&Lut>,
+) -> termwiz::color::SrgbaTuple {
+    let graded = colour
+        .saturate(config.saturation.into())
+        .lighten(config.brightness.into())
+        .adjust_hue_fixed(config.hue.into());
+    let termwiz::color::SrgbaTuple(red, green, blue, alpha) = graded;
+
+    let mut rgb = adjust([red, green, blue], config);
+    if let Some(lut) = maybe_lut {
+        rgb = lut.apply(rgb);
+    }
+
+    let [graded_red, graded_green, graded_blue] = rgb;
+    termwiz::color::SrgbaTuple(graded_red, graded_green, graded_blue, alpha)
+}
+
+/// Apply contrast, gamma, temperature and tint to the red, green and blue channels of a colour.
+#[must_use]
+pub(crate) fn adjust(rgb: [f32; 3], config: &crate::config::main::Color) -> [f32; 3] {
+    let temperature = config.temperature * TEMPERATURE_STRENGTH;
+    let tint = config.tint * TEMPERATURE_STRENGTH;
+    let channel_scales = [1.0 + temperature, 1.0 - tint, 1.0 - temperature];
+
+    let mut adjusted = rgb;
+    for (value, scale) in adjusted.iter_mut().zip(channel_scales) {
+        let mut channel = (*value - 0.5).mul_add(1.0 + config.contrast, 0.5);
+        if config.gamma > 0.0 {
+            channel = channel.clamp(0.0, 1.0).powf(config.gamma.recip());
+        }
+        *value = (channel * scale).clamp(0.0, 1.0);
+    }
+    adjusted
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const IDENTITY_LUT: &str = "
+        # An identity LUT
+        TITLE \"Identity\"
+        LUT_3D_SIZE 2
+
+        0 0 0
+        1 0 0
+        0 1 0
+        1 1 0
+        0 0 1
+        1 0 1
+        0 1 1
+        1 1 1
+    ";
+
+    fn assert_colour(actual: [f32; 3], expected: [f32; 3]) {
+        for (actual_value, expected_value) in actual.iter().zip(expected) {
+            assert!(
+                (actual_value - expected_value).abs() < 0.0001,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn identity_lut() {
+        let lut = Lut::parse(IDENTITY_LUT).unwrap();
+
+        assert_colour(lut.apply([0.25, 0.5, 0.75]), [0.25, 0.5, 0.75]);
+        assert_colour(lut.apply([1.0, 0.0, 1.0]), [1.0, 0.0, 1.0]);
+        assert_colour(lut.apply([2.0, -1.0, 0.5]), [1.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn inverting_lut_with_domain() {
+        let contents = "
+            LUT_3D_SIZE 2
+            DOMAIN_MIN 0 0 0
+            DOMAIN_MAX 2 2 2
+            1 1 1
+            0 1 1
+            1 0 1
+            0 0 1
+            1 1 0
+            0 1 0
+            1 0 0
+            0 0 0
+        ";
+        let lut = Lut::parse(contents).unwrap();
+
+        assert_colour(lut.apply([0.5, 1.0, 2.0]), [0.75, 0.5, 0.0]);
+    }
+
+    /// A LUT like the ones that DaVinci Resolve exports.
+    const RESOLVE_LUT: &str = "
+        TITLE \"Half brightness\"
+        LUT_3D_SIZE 2
+        LUT_3D_INPUT_RANGE 0.0 2.0
+        LUT_IN_VIDEO_RANGE
+
+        0 0 0
+        1 0 0
+        0 1 0
+        1 1 0
+        0 0 1
+        1 0 1
+        0 1 1
+        1 1 1
+    ";
+
+    #[test]
+    fn lut_with_input_range_and_unknown_keywords() {
+        let lut = Lut::parse(RESOLVE_LUT).unwrap();
+        assert_colour(lut.apply([1.0, 0.5, 2.0]), [0.5, 0.25, 1.0]);
+
+        let with_domain = RESOLVE_LUT.replace(
+            "LUT_3D_INPUT_RANGE 0.0 2.0",
+            "DOMAIN_MIN 0.0 0.0 0.0\nDOMAIN_MAX 2.0 2.0 2.0",
+        );
+        let lut = Lut::parse(&with_domain).unwrap();
+        assert_colour(lut.apply([1.0, 0.5, 2.0]), [0.5, 0.25, 1.0]);
+    }
+
+    #[test]
+    fn invalid_luts() {
+        let missing_entry = IDENTITY_LUT.replace("1 1 1", "");
+        assert_eq!(
+            Lut::parse(&missing_entry).unwrap_err().to_string(),
+            "LUT of size 2 should have 8 entries, but has 7"
+        );
+        assert_eq!(
+            Lut::parse("LUT_1D_SIZE 2").unwrap_err().to_string(),
+            "1D LUTs aren't supported, only 3D LUTs"
+        );
+        assert_eq!(
+            Lut::parse("0 0 0").unwrap_err().to_string(),
+            "LUT is missing its `LUT_3D_SIZE`"
+        );
+        assert_eq!(
+            Lut::parse(&RESOLVE_LUT.replace("0.0 2.0", "0.0"))
+                .unwrap_err()
+                .to_string(),
+            "LUT's `LUT_3D_INPUT_RANGE` needs a minimum and a maximum"
+        );
+        assert_eq!(
+            Lut::parse(&RESOLVE_LUT.replace("0.0 2.0", "2.0 0.0"))
+                .unwrap_err()
+                .to_string(),
+            "LUT's `DOMAIN_MIN` must be less than its `DOMAIN_MAX`"
+        );
+    }
+
+    #[test]
+    fn adjustments() {
+        let neutral = crate::config::main::Color::default();
+        assert_colour(adjust([0.2, 0.5, 0.8], &neutral), [0.2, 0.5, 0.8]);
+
+        let contrast = crate::config::main::Color {
+            contrast: 1.0,
+            ..Default::default()
+        };
+        assert_colour(adjust([0.25, 0.5, 0.8], &contrast), [0.0, 0.5, 1.0]);
+
+        let gamma = crate::config::main::Color {
+            gamma: 2.0,
+            ..Default::default()
+        };
+        assert_colour(adjust([0.25, 0.0, 1.0], &gamma), [0.5, 0.0, 1.0]);
+
+        let warm = crate::config::main::Color {
+            temperature: 1.0,
+            tint: 1.0,
+            ..Default::default()
+        };
+        assert_colour(adjust([0.5, 0.5, 0.5], &warm), [0.6, 0.4, 0.4]);
+    }
+}
@@ -54,7 +54,13 @@ impl Compositor {
         }
 
         let mut draft = termwiz::cell::Cell::blank();
-        Self::composite_cells(&mut draft, cell_above, 1.0, default_bg_colour);
+        Self::composite_cells(
+            &mut draft,
+            cell_above,
+            1.0,
+            crate::blender::BlendMode::Normal,
+            default_bg_colour,
+        );
         let colour = draft.attrs().foreground();
         base_cell.attrs_mut().set_foreground(colour);
     }
@@ -66,7 +72,12 @@ impl Compositor {
         opacity: f32,
         default_bg_colour: termwiz::color::SrgbaTuple,
     ) {
-        let mut blender = crate::blender::Blender::new(base_cell, default_bg_colour, opacity);
+        let mut blender = crate::blender::Blender::new(
+            base_cell,
+            default_bg_colour,
+            opacity,
+            crate::blender::BlendMode::Normal,
+        );
         let maybe_foreground =
             crate::blender::Blender::extract_colour(cell_above.attrs().foreground());
         let maybe_background =
@@ -91,6 +102,7 @@ impl Compositor {
         composited_cell: &mut termwiz::cell::Cell,
         cell_above: &termwiz::cell::Cell,
         opacity: f32,
+        blend_mode: crate::blender::BlendMode,
         default_bg_colour: termwiz::color::SrgbaTuple,
     ) {
         let character_above = cell_above.str();
@@ -110,7 +122,8 @@ impl Compositor {
             composited_cell.attrs_mut().set_background(background);
         }
 
-        let mut blender = crate::blender::Blender::new(composited_cell, default_bg_colour, opacity);
+        let mut blender =
+            crate::blender::Blender::new(composited_cell, default_bg_colour, opacity, blend_mode);
         blender.blend_all(cell_above);
 
         // The convention we use for pixel graphics is that we always try to render using the upper
@@ -129,7 +142,12 @@ impl Compositor {
         apply_to_readable_text_only: bool,
         default_bg_colour: termwiz::color::SrgbaTuple,
     ) {
-        let mut blender = crate::blender::Blender::new(composited_cell, default_bg_colour, 1.0);
+        let mut blender = crate::blender::Blender::new(
+            composited_cell,
+            default_bg_colour,
+            1.0,
+            crate::blender::BlendMode::Normal,
+        );
         blender.ensure_readable_contrast(target_text_contrast, apply_to_readable_text_only);
     }
 
@@ -142,7 +160,13 @@ impl Compositor {
         default_bg_colour: termwiz::color::SrgbaTuple,
     ) -> Result<()> {
         let composited_cell = Self::get_cell_mut(cells, x, y)?;
-        Self::composite_cells(composited_cell, indicator_cell, 1.0, default_bg_colour);
+        Self::composite_cells(
+            composited_cell,
+            indicator_cell,
+            1.0,
+            crate::blender::BlendMode::Normal,
+            default_bg_colour,
+        );
 
         Ok(())
     }
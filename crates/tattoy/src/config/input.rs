@@ -21,6 +21,8 @@ pub(crate) enum KeybindingAction {
     ToggleScrolling,
     /// Show/hide the minimap.
     ToggleMinimap,
+    /// Show the STDERR output of each plugin in turn, and then hide it.
+    TogglePluginLog,
     /// Scroll up. Also triggers scroll mode if it's not currently enabled.
     ScrollUp,
     /// Scroll down.
@@ -40,6 +42,10 @@ pub(crate) type KeybindingsRaw = std::collections::HashMap<KeybindingAction, Key
 pub(crate) type KeybindingsAsEvents =
     std::collections::HashMap<KeybindingAction, termwiz::input::KeyEvent>;
 
+/// The keybindings claimed by plugins, keyed by the plugin's name.
+pub(crate) type PluginKeybindings =
+    std::collections::HashMap<String, Vec<termwiz::input::KeyEvent>>;
+
 impl TryFrom<KeybindingConfigRaw> for termwiz::input::KeyEvent {
     type Error = std::io::Error;
 
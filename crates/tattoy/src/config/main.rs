@@ -64,6 +64,8 @@ pub(crate) struct Config {
     pub keybindings: super::input::KeybindingsRaw,
     /// Target frame rate
     pub frame_rate: u32,
+    /// Slowing down animations when the terminal isn't being used.
+    pub idle: Idle,
     /// Whether to show the little tattoy indicator in the top-right of the terminal.
     pub show_tattoy_indicator: bool,
     /// Whether to show the startup logo.
@@ -112,6 +114,7 @@ impl Default for Config {
             log_level: LogLevel::Off,
             log_path,
             frame_rate: 30,
+            idle: Idle::default(),
             keybindings: super::input::KeybindingsRaw::new(),
             show_tattoy_indicator: true,
             show_startup_logo: true,
@@ -130,6 +133,7 @@ impl Default for Config {
 
 /// Final colour grading for the whole terminal render.
 #[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default)]
 pub(crate) struct Color {
     /// Saturation
     pub saturation: f32,
@@ -137,6 +141,16 @@ pub(crate) struct Color {
     pub brightness: f32,
     /// Hue
     pub hue: f32,
+    /// Contrast, where 0.0 is unchanged.
+    pub contrast: f32,
+    /// Gamma, where 1.0 is unchanged.
+    pub gamma: f32,
+    /// Colour temperature. Positive is warmer, negative is cooler.
+    pub temperature: f32,
+    /// Tint. Positive is more magenta, negative is more green.
+    pub tint: f32,
+    /// Path to a `.cube` 3D LUT file. Relative to the root of Tattoy's config directory.
+    pub lut: Option<std::path::PathBuf>,
 }
 
 impl Default for Color {
@@ -145,6 +159,11 @@ impl Default for Color {
             saturation: 0.0,
             brightness: 0.0,
             hue: 0.0,
+            contrast: 0.0,
+            gamma: 1.0,
+            temperature: 0.0,
+            tint: 0.0,
+            lut: None,
         }
     }
 }
@@ -169,7 +188,40 @@ impl Default for TextContrast {
         }
     }
 }
+/// Config for slowing down animations when the terminal isn't being used.
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default)]
+pub(crate) struct Idle {
+    /// Whether to slow down animations when the terminal is idle.
+    pub enabled: bool,
+    /// The number of seconds without any PTY output or user input before the terminal is idle.
+    pub timeout: u64,
+    /// The frame rate of animations whilst the terminal is idle.
+    pub frame_rate: u32,
+}
+
+impl Default for Idle {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout: 60,
+            frame_rate: 2,
+        }
+    }
+}
+
 impl Config {
+    /// The frame rate of a tattoy, some tattoys can override the global frame rate.
+    pub fn frame_rate_for(&self, tattoy_id: &str) -> u32 {
+        let maybe_frame_rate = match tattoy_id {
+            "shader" => self.shader.frame_rate,
+            "animated_cursor" => self.animated_cursor.frame_rate,
+            "minimap" => self.minimap.frame_rate,
+            _ => None,
+        };
+        maybe_frame_rate.unwrap_or(self.frame_rate)
+    }
+
     /// Canonical path to the config directory.
     pub async fn directory(
         state: &std::sync::Arc<crate::shared_state::SharedState>,
@@ -77,6 +77,12 @@ pub(crate) fn spawn(
                 Arc::clone(&state),
             ));
 
+            tracing::info!("Starting 'plugin_log' tattoy...");
+            tattoy_futures.spawn(crate::tattoys::plugin_log::PluginLog::start(
+                output.clone(),
+                Arc::clone(&state),
+            ));
+
             if enabled_tattoys.contains(&"random_walker".to_owned()) {
                 tracing::info!("Starting 'random_walker' tattoy...");
                 tattoy_futures.spawn(crate::tattoys::random_walker::RandomWalker::start(
@@ -118,40 +124,52 @@ pub(crate) fn spawn(
                 ));
             }
 
-            for plugin_config in &state.config.read().await.plugins {
-                if let Some(is_enabled) = plugin_config.enabled {
-                    if !is_enabled {
-                        continue;
-                    }
-                }
-
-                tattoy_futures.spawn(crate::tattoys::plugins::Plugin::start(
-                    plugin_config.clone(),
-                    palette.clone(),
-                    Arc::clone(&state),
-                    output.clone(),
-                ));
+            // Subscribe before starting any plugins so that no config reloads are missed.
+            let mut protocol_rx = state.protocol_tx.subscribe();
+            let mut plugin_configs = state.config.read().await.plugins.clone();
+            let mut running_plugins = std::collections::HashMap::new();
+            for plugin_config in plugin_configs.iter().filter(|config| config.is_enabled()) {
+                spawn_plugin(
+                    &mut tattoy_futures,
+                    &mut running_plugins,
+                    plugin_config,
+                    &palette,
+                    &output,
+                    &state,
+                );
             }
 
-            while let Some(completes) = tattoy_futures.join_next().await {
-                match completes {
-                    Ok(result) => match result {
-                        Ok(()) => tracing::debug!("A tattoy succesfully exited"),
-                        Err(error) => {
-                            let title = "Unhandled tattoy error";
-                            let message = format!("{title}: {error:?}");
-                            tracing::warn!(message);
-                            state
-                                .send_notification(
-                                    title,
-                                    crate::tattoys::notifications::message::Level::Error,
-                                    Some(error.root_cause().to_string()),
-                                    true,
-                                )
-                                .await;
-                        }
+            #[expect(
+                clippy::integer_division_remainder_used,
+                reason = "This is caused by the `tokio::select!`"
+            )]
+            loop {
+                tokio::select! {
+                    maybe_completes = tattoy_futures.join_next_with_id() => {
+                        let Some(completes) = maybe_completes else {
+                            break;
+                        };
+                        let task_id = match &completes {
+                            Ok((task_id, _)) => *task_id,
+                            Err(error) => error.id(),
+                        };
+                        running_plugins.remove(&task_id);
+                        handle_tattoy_exit(completes.map(|(_, result)| result), &state).await;
                     },
-                    Err(error) => tracing::error!("Tattoy task join error: {error:?}"),
+                    Ok(message) = protocol_rx.recv() => {
+                        if let crate::run::Protocol::Config(config) = message {
+                            start_newly_enabled_plugins(
+                                &mut tattoy_futures,
+                                &mut running_plugins,
+                                &plugin_configs,
+                                &config.plugins,
+                                &output,
+                                &state,
+                            )
+                            .await;
+                            plugin_configs = config.plugins;
+                        }
+                    }
                 }
             }
 
@@ -160,6 +178,90 @@ pub(crate) fn spawn(
     })
 }
 
+/// Start a plugin, keeping track of its task so that we know whether it's still running.
+fn spawn_plugin(
+    tattoy_futures: &mut tokio::task::JoinSet<Result<()>>,
+    running_plugins: &mut std::collections::HashMap<tokio::task::Id, String>,
+    plugin_config: &crate::tattoys::plugins::Config,
+    palette: &crate::palette::converter::Palette,
+    output: &tokio::sync::mpsc::Sender<FrameUpdate>,
+    state: &Arc<crate::shared_state::SharedState>,
+) {
+    let task = tattoy_futures.spawn(crate::tattoys::plugins::Plugin::start(
+        plugin_config.clone(),
+        palette.clone(),
+        Arc::clone(state),
+        output.clone(),
+    ));
+    running_plugins.insert(task.id(), plugin_config.name().to_owned());
+}
+
+/// Start any plugins that have been enabled since the config was last loaded, or that had failed
+/// to start but have since had their config changed. Running plugins stop themselves when they're
+/// disabled.
+async fn start_newly_enabled_plugins(
+    tattoy_futures: &mut tokio::task::JoinSet<Result<()>>,
+    running_plugins: &mut std::collections::HashMap<tokio::task::Id, String>,
+    previous: &[crate::tattoys::plugins::Config],
+    reloaded: &[crate::tattoys::plugins::Config],
+    output: &tokio::sync::mpsc::Sender<FrameUpdate>,
+    state: &Arc<crate::shared_state::SharedState>,
+) {
+    let running = running_plugins
+        .values()
+        .map(String::as_str)
+        .collect::<Vec<&str>>();
+    let newly_enabled =
+        crate::tattoys::plugins::Config::newly_enabled(previous, reloaded, &running);
+    if newly_enabled.is_empty() {
+        return;
+    }
+
+    let palette = match crate::config::main::Config::load_palette(Arc::clone(state)).await {
+        Ok(palette) => palette,
+        Err(error) => {
+            tracing::error!("Couldn't load palette for newly enabled plugins: {error:?}");
+            return;
+        }
+    };
+    for plugin_config in newly_enabled {
+        spawn_plugin(
+            tattoy_futures,
+            running_plugins,
+            plugin_config,
+            &palette,
+            output,
+            state,
+        );
+    }
+}
+
+/// Report how a tattoy exited.
+async fn handle_tattoy_exit(
+    completes: Result<Result<()>, tokio::task::JoinError>,
+    state: &Arc<crate::shared_state::SharedState>,
+) {
+    match completes {
+        Ok(result) => match result {
+            Ok(()) => tracing::debug!("A tattoy succesfully exited"),
+            Err(error) => {
+                let title = "Unhandled tattoy error";
+                let message = format!("{title}: {error:?}");
+                tracing::warn!(message);
+                state
+                    .send_notification(
+                        title,
+                        crate::tattoys::notifications::message::Level::Error,
+                        Some(error.root_cause().to_string()),
+                        true,
+                    )
+                    .await;
+            }
+        },
+        Err(error) => tracing::error!("Tattoy task join error: {error:?}"),
+    }
+}
+
 /// Wait for tattoys that need to be running before the PTY starts.
 async fn wait_for_enabled_tattoys_to_start(
     enabled_tattoys: Vec<String>,
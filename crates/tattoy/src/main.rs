@@ -11,6 +11,7 @@ pub mod config {
     pub mod main;
 }
 pub mod blender;
+pub mod colour_grading;
 pub mod compositor;
 pub mod loader;
 pub mod raw_input;
@@ -30,6 +31,8 @@ pub mod surface;
 pub mod terminal_proxy {
     pub mod input_handler;
     pub mod proxy;
+    pub mod tapped_terminal;
+    pub mod working_directory;
 }
 pub mod utils;
 
@@ -46,6 +49,8 @@ pub mod tattoys {
         pub mod message;
     }
 
+    pub mod plugin_log;
+    pub mod plugin_outbox;
     pub mod plugins;
     pub mod random_walker;
     pub mod scrollbar;
@@ -60,6 +65,7 @@ pub mod tattoys {
     }
 
     pub mod tattoyer;
+    pub mod wasm_plugin;
 }
 
 use color_eyre::eyre::Result;
@@ -14,6 +14,7 @@ use termwiz::terminal::Terminal as _;
 use crate::compositor::Compositor;
 use crate::run::FrameUpdate;
 use crate::shared_state::SharedState;
+use crate::surface::Rect;
 
 /// The number of microseconds in a second.
 pub const ONE_MICROSECOND: u64 = 1_000_000;
@@ -43,6 +44,21 @@ pub const CHECK_FOR_RESIZE_RATE: u64 = 30;
 /// buffer of frames is for extreme conditions. 100 frames should give about 3 seconds of grace.
 const MAX_FRAME_BACKLOG: usize = 100;
 
+/// The fraction of time that the renderer can spend painting frames before animations are
+/// throttled.
+///
+/// Each animated tattoy can trigger a paint for every one of its frames. So on large terminals, or
+/// with many animated tattoys, painting can take up all of the renderer's time. Then the user's
+/// terminal becomes sluggish. Keeping a margin means there's always time to paint the user's own
+/// typing.
+const PAINT_BUDGET: f32 = 0.5;
+
+/// How often the renderer's load is measured in order to adjust the throttle.
+const LOAD_MEASUREMENT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// The most that animations are slowed down by when the renderer can't keep up.
+const MAX_THROTTLE: f32 = 10.0;
+
 /// `Render`
 pub(crate) struct Renderer {
     /// Shared app state
@@ -65,6 +81,21 @@ pub(crate) struct Renderer {
     pub is_cursor_visible: bool,
     /// Default background colour
     pub default_bg_colour: termwiz::color::SrgbaTuple,
+    /// The part of the frame that has changed since it was last composited. Only this part is
+    /// recomposited and sent to the user's terminal.
+    pub damage: Rect,
+    /// The position of the cursor when the frame was last composited.
+    pub cursor_position: (usize, usize),
+    /// Whether rendering was enabled when the frame was last composited.
+    pub was_rendering_enabled: bool,
+    /// Whether a tattoy was replacing the PTY layer when the frame was last composited.
+    pub was_pty_replaced: bool,
+    /// The time spent painting since the renderer's load was last measured.
+    pub paint_time: std::time::Duration,
+    /// When the renderer's load was last measured.
+    pub load_measured_at: std::time::Instant,
+    /// The user's colour grading LUT, if they've configured one.
+    pub lut: Option<crate::colour_grading::Lut>,
 }
 
 impl Renderer {
@@ -83,7 +114,7 @@ impl Renderer {
             None
         };
 
-        let renderer = Self {
+        let mut renderer = Self {
             state,
             width: size.width,
             height: size.height,
@@ -94,7 +125,15 @@ impl Renderer {
             indicator_cell: Self::indicator_cell()?,
             is_cursor_visible: true,
             default_bg_colour,
+            damage: Rect::new(0, 0, width.into(), height.into()),
+            cursor_position: (0, 0),
+            was_rendering_enabled: false,
+            was_pty_replaced: false,
+            paint_time: std::time::Duration::ZERO,
+            load_measured_at: std::time::Instant::now(),
+            lut: None,
         };
+        renderer.load_lut().await;
 
         Ok(renderer)
     }
@@ -273,29 +312,98 @@ impl Renderer {
             | crate::run::Protocol::End
             | crate::run::Protocol::Resize { .. }
             | crate::run::Protocol::Input(_)
-            | crate::run::Protocol::Config(_)
+            | crate::run::Protocol::PTYInput(_)
             | crate::run::Protocol::KeybindEvent(_)
             | crate::run::Protocol::Notification(_) => (),
+            // Any of the config can affect how cells are composited.
+            crate::run::Protocol::Config(_) => {
+                self.load_lut().await;
+                self.damage_everything();
+            }
             crate::run::Protocol::CursorVisibility(is_visible) => {
+                if self.is_cursor_visible != *is_visible {
+                    let (cursor_x, cursor_y) = self.cursor_position;
+                    self.add_damage(Rect::new(cursor_x, cursor_y, 1, 1));
+                }
                 self.is_cursor_visible = *is_visible;
             }
+            // Anything that changes the frame already adds damage, so there's no need to
+            // recomposite the whole frame.
             crate::run::Protocol::Repaint => self.paint().await?,
         }
 
         Ok(())
     }
 
-    /// Reset the frame for every render.
+    /// Reset the frame, for instance when the user's terminal has been resized.
     fn reset_frame(&mut self) {
         self.frame = TermwizSurface::new(self.width.into(), self.height.into());
     }
 
+    /// Blank the damaged cells of the frame, ready for them to be recomposited.
+    fn reset_damaged_cells(&mut self, damage: Rect) {
+        for line in self
+            .frame
+            .screen_cells()
+            .iter_mut()
+            .skip(damage.y)
+            .take(damage.height)
+        {
+            for cell in line.iter_mut().skip(damage.x).take(damage.width) {
+                *cell = termwiz::cell::Cell::default();
+            }
+        }
+    }
+
+    /// The size of the user's terminal.
+    fn frame_size(&self) -> (usize, usize) {
+        (self.width.into(), self.height.into())
+    }
+
+    /// Mark part of the frame as needing to be recomposited.
+    fn add_damage(&mut self, rect: Rect) {
+        self.damage = self.damage.union(rect);
+    }
+
+    /// Mark the whole frame as needing to be recomposited.
+    fn damage_everything(&mut self) {
+        let (width, height) = self.frame_size();
+        self.damage = Rect::new(0, 0, width, height);
+    }
+
+    /// Work out which part of the frame is changed by a new tattoy surface.
+    fn tattoy_damage(
+        previous: Option<&crate::surface::Surface>,
+        current: &crate::surface::Surface,
+        frame_size: (usize, usize),
+    ) -> Rect {
+        let is_removed = current.width == 0 || current.height == 0;
+        let current_area = if is_removed {
+            Rect::default()
+        } else {
+            current.area(frame_size)
+        };
+        let Some(previous_surface) = previous else {
+            return current_area;
+        };
+
+        let previous_area = previous_surface.area(frame_size);
+        if is_removed || previous_area != current_area {
+            return previous_area.union(current_area);
+        }
+
+        current.damage.within(current_area)
+    }
+
     /// Do a single render to the user's actual terminal. It uses a diffing algorithm to make
     /// the minimum number of changes.
     async fn render(&mut self, backlog: usize, update: FrameUpdate) -> Result<()> {
         match update {
             FrameUpdate::TattoySurface(surface) => {
                 let surface_id = surface.id.clone();
+                let damage =
+                    Self::tattoy_damage(self.tattoys.get(&surface_id), &surface, self.frame_size());
+                self.add_damage(damage);
                 if surface.width == 0 || surface.height == 0 {
                     self.tattoys.remove(&surface_id);
                 } else {
@@ -330,7 +438,8 @@ impl Renderer {
 
     /// Apply the changes to the user's terminal.
     async fn paint(&mut self) -> Result<()> {
-        self.composite().await?;
+        let started_at = std::time::Instant::now();
+        let damage = self.composite().await?;
 
         let Some(users_terminal) = self.users_terminal.as_mut() else {
             return Ok(());
@@ -341,8 +450,19 @@ impl Renderer {
             termwiz::surface::CursorVisibility::Hidden,
         ));
 
-        let changes = users_terminal.diff_screens(&self.frame);
-        users_terminal.add_changes(changes);
+        // Only the damaged part of the frame can differ from what's already on the user's screen.
+        if !damage.is_empty() {
+            let changes = users_terminal.diff_region(
+                damage.x,
+                damage.y,
+                damage.width,
+                damage.height,
+                &self.frame,
+                damage.x,
+                damage.y,
+            );
+            users_terminal.add_changes(changes);
+        }
 
         let (cursor_x, cursor_y) = self.pty.cursor_position();
         users_terminal.add_change(TermwizChange::CursorPosition {
@@ -372,36 +492,97 @@ impl Renderer {
         // This is where we actually render to the user's real terminal.
         users_terminal.flush()?;
 
+        self.paint_time += started_at.elapsed();
+        self.update_throttle().await;
+
         Ok(())
     }
 
+    /// Slow down animations when painting takes up more than its share of the renderer's time,
+    /// and speed them back up once it doesn't.
+    async fn update_throttle(&mut self) {
+        let elapsed = self.load_measured_at.elapsed();
+        if elapsed < LOAD_MEASUREMENT_INTERVAL {
+            return;
+        }
+        let load = self.paint_time.as_secs_f32() / elapsed.as_secs_f32();
+        self.paint_time = std::time::Duration::ZERO;
+        self.load_measured_at = std::time::Instant::now();
+
+        let mut throttle = self.state.throttle.write().await;
+        let next_throttle = Self::next_throttle(*throttle, load);
+        if (next_throttle - *throttle).abs() > f32::EPSILON {
+            tracing::debug!(
+                "Renderer load is {:.0}%, throttling animations by {next_throttle:.1}x",
+                load * 100.0
+            );
+            *throttle = next_throttle;
+        }
+    }
+
+    /// Throttling animations reduces the load by roughly the same factor. So scaling the throttle
+    /// by how far the load is from its budget should bring the load back to the budget.
+    fn next_throttle(throttle: f32, load: f32) -> f32 {
+        (throttle * load / PAINT_BUDGET).clamp(1.0, MAX_THROTTLE)
+    }
+
     // TODO: A failed render shouldn't crash the whole tick.
-    /// Composite all the tattoys and the PTY together into a single surface (frame).
-    pub async fn composite(&mut self) -> Result<()> {
+    /// Composite all the tattoys and the PTY together into a single surface (frame). Only the
+    /// damaged part of the frame is recomposited, the rest is left as it was. Returns the part of
+    /// the frame that was recomposited.
+    pub async fn composite(&mut self) -> Result<Rect> {
         let is_rendering_enabled = *self.state.is_rendering_enabled.read().await;
-        self.reset_frame();
+        let is_pty_replaced = self.is_a_plugin_replacing_the_pty_layer() && is_rendering_enabled;
+
+        let frame_size = self.frame_size();
+        if self.frame.dimensions() != frame_size
+            || is_rendering_enabled != self.was_rendering_enabled
+            || is_pty_replaced != self.was_pty_replaced
+        {
+            self.reset_frame();
+            self.damage_everything();
+        }
+        self.was_rendering_enabled = is_rendering_enabled;
+        self.was_pty_replaced = is_pty_replaced;
+
+        // The cursor's cell is cleaned, so moving the cursor changes both its old and new cells.
+        let cursor = self.pty.cursor_position();
+        if cursor != self.cursor_position {
+            let (previous_x, previous_y) = self.cursor_position;
+            self.add_damage(Rect::new(previous_x, previous_y, 1, 1));
+            self.add_damage(Rect::new(cursor.0, cursor.1, 1, 1));
+            self.cursor_position = cursor;
+        }
+
+        let damage = std::mem::take(&mut self.damage).clip(frame_size.0, frame_size.1);
+        if damage.is_empty() {
+            return Ok(damage);
+        }
+        self.reset_damaged_cells(damage);
 
         if is_rendering_enabled {
-            self.render_tattoys_below().await?;
+            self.render_tattoys_below(damage).await?;
         }
 
-        if self.is_a_plugin_replacing_the_pty_layer() && is_rendering_enabled {
-            self.render_tattoys(std::cmp::Ordering::Equal).await?;
+        if is_pty_replaced {
+            self.render_tattoys(std::cmp::Ordering::Equal, damage)
+                .await?;
         } else {
-            self.render_pty().await?;
+            self.render_pty(damage).await?;
         }
 
         if is_rendering_enabled {
-            self.render_tattoys_above().await?;
-            self.colour_grade().await?;
-            self.add_indicator().await?;
-            if self.is_cursor_visible {
-                let cursor = self.pty.cursor_position();
+            self.render_tattoys_above(damage).await?;
+            self.colour_grade(damage).await?;
+            if damage.contains(frame_size.0.saturating_sub(1), 0) {
+                self.add_indicator().await?;
+            }
+            if self.is_cursor_visible && damage.contains(cursor.0, cursor.1) {
                 Compositor::clean_cursor_cell(&mut self.frame.screen_cells(), cursor.0, cursor.1);
             }
         }
 
-        Ok(())
+        Ok(damage)
     }
 
     /// Add the little blue pixel in the top right.
@@ -425,17 +606,18 @@ impl Renderer {
     }
 
     /// Render all the tattoys that appear below the PTY.
-    async fn render_tattoys_below(&mut self) -> Result<()> {
-        self.render_tattoys(std::cmp::Ordering::Less).await
+    async fn render_tattoys_below(&mut self, damage: Rect) -> Result<()> {
+        self.render_tattoys(std::cmp::Ordering::Less, damage).await
     }
 
     /// Render all the tattoys that appear above the PTY.
-    async fn render_tattoys_above(&mut self) -> Result<()> {
-        self.render_tattoys(std::cmp::Ordering::Greater).await
+    async fn render_tattoys_above(&mut self, damage: Rect) -> Result<()> {
+        self.render_tattoys(std::cmp::Ordering::Greater, damage)
+            .await
     }
 
-    /// Render a tattoy onto the compositor frame.
-    async fn render_tattoys(&mut self, comparator: std::cmp::Ordering) -> Result<()> {
+    /// Render the damaged part of each tattoy onto the compositor frame.
+    async fn render_tattoys(&mut self, comparator: std::cmp::Ordering, damage: Rect) -> Result<()> {
         let mut tattoys: Vec<&mut crate::surface::Surface> = self
             .tattoys
             .values_mut()
@@ -454,21 +636,52 @@ impl Renderer {
             }
 
             let tattoy_frame_size = tattoy.surface.dimensions();
-            if tattoy_frame_size != frame_size {
-                tracing::warn!(
-                    "Not rendering '{}' as its size doesn't match the current frame size",
-                    tattoy.id
-                );
-                continue;
-            }
+            let (origin_x, origin_y) = match tattoy.region {
+                Some(region) => region.origin(tattoy_frame_size, frame_size),
+                None => {
+                    if tattoy_frame_size != frame_size {
+                        tracing::warn!(
+                            "Not rendering '{}' as its size doesn't match the current frame size",
+                            tattoy.id
+                        );
+                        continue;
+                    }
+                    (0, 0)
+                }
+            };
             let tattoy_cells = tattoy.surface.get_screen_cells();
 
-            for (frame_line, tattoy_line) in frame_cells.iter_mut().zip(tattoy_cells) {
-                for (frame_cell, tattoy_cell) in frame_line.iter_mut().zip(tattoy_line) {
+            // Any part of a region that doesn't fit in the frame is never looked up, so it's
+            // clipped.
+            for (y, frame_line) in frame_cells
+                .iter_mut()
+                .enumerate()
+                .skip(damage.y)
+                .take(damage.height)
+            {
+                let Some(tattoy_line) = y
+                    .checked_sub(origin_y)
+                    .and_then(|row| tattoy_cells.get(row))
+                else {
+                    continue;
+                };
+                for (x, frame_cell) in frame_line
+                    .iter_mut()
+                    .enumerate()
+                    .skip(damage.x)
+                    .take(damage.width)
+                {
+                    let Some(tattoy_cell) = x
+                        .checked_sub(origin_x)
+                        .and_then(|column| tattoy_line.get(column))
+                    else {
+                        continue;
+                    };
                     Compositor::composite_cells(
                         frame_cell,
                         tattoy_cell,
                         tattoy.opacity,
+                        tattoy.blend_mode,
                         self.default_bg_colour,
                     );
                 }
@@ -478,8 +691,8 @@ impl Renderer {
         Ok(())
     }
 
-    /// Render the PTY to the compositor frame.
-    async fn render_pty(&mut self) -> Result<()> {
+    /// Render the damaged part of the PTY to the compositor frame.
+    async fn render_pty(&mut self, damage: Rect) -> Result<()> {
         let frame_size = self.frame.dimensions();
         let mut frame_cells = self.frame.screen_cells();
 
@@ -512,9 +725,27 @@ impl Renderer {
         let is_rendering = *self.state.is_rendering_enabled.read().await;
         let animated_cursor_opacity = self.state.config.read().await.animated_cursor.opacity;
 
-        for (y, (frame_line, pty_line)) in frame_cells.iter_mut().zip(pty_cells).enumerate() {
-            for (x, (frame_cell, pty_cell)) in frame_line.iter_mut().zip(pty_line).enumerate() {
-                Compositor::composite_cells(frame_cell, pty_cell, 1.0, self.default_bg_colour);
+        for (y, (frame_line, pty_line)) in frame_cells
+            .iter_mut()
+            .zip(pty_cells)
+            .enumerate()
+            .skip(damage.y)
+            .take(damage.height)
+        {
+            for (x, (frame_cell, pty_cell)) in frame_line
+                .iter_mut()
+                .zip(pty_line)
+                .enumerate()
+                .skip(damage.x)
+                .take(damage.width)
+            {
+                Compositor::composite_cells(
+                    frame_cell,
+                    pty_cell,
+                    1.0,
+                    crate::blender::BlendMode::Normal,
+                    self.default_bg_colour,
+                );
 
                 if !is_rendering {
                     continue;
@@ -583,7 +814,13 @@ impl Renderer {
                 default_bg_colour,
             );
         } else {
-            Compositor::composite_cells(frame_cell, &cursor_cell, 1.0, default_bg_colour);
+            Compositor::composite_cells(
+                frame_cell,
+                &cursor_cell,
+                1.0,
+                crate::blender::BlendMode::Normal,
+                default_bg_colour,
+            );
         }
 
         Ok(())
@@ -614,49 +851,75 @@ impl Renderer {
 
     /// Fetch the freshly made PTY frame from the shared state.
     async fn get_updated_pty_frame(&mut self) {
-        self.pty.resize(self.width.into(), self.height.into());
         let surface = self.state.shadow_tty_screen.read().await;
         let (cursor_x, cursor_y) = surface.cursor_position();
-        self.pty = surface.clone();
+        let previous = std::mem::replace(&mut self.pty, surface.clone());
         drop(surface);
 
+        let (width, height) = self.frame_size();
+        let damage = crate::surface::Damage::between(&previous, &self.pty);
+        self.add_damage(damage.within(Rect::new(0, 0, width, height)));
+
         self.pty.add_change(TermwizChange::CursorPosition {
             x: TermwizPosition::Absolute(cursor_x),
             y: TermwizPosition::Absolute(cursor_y),
         });
     }
 
+    /// Load the user's colour grading LUT. Errors are shown to the user rather than stopping
+    /// Tattoy, they'll probably want to fix the LUT file and try again.
+    async fn load_lut(&mut self) {
+        let maybe_path = self.state.config.read().await.color.lut.clone();
+        self.lut = None;
+        let Some(path) = maybe_path else {
+            return;
+        };
+
+        let config_directory = crate::config::main::Config::directory(&self.state).await;
+        match crate::colour_grading::Lut::load(&config_directory.join(path)) {
+            Ok(lut) => self.lut = Some(lut),
+            Err(error) => {
+                tracing::error!("Couldn't load colour grading LUT: {error:?}");
+                self.state
+                    .send_notification(
+                        "Couldn't load colour grading LUT",
+                        crate::tattoys::notifications::message::Level::Error,
+                        Some(error.root_cause().to_string()),
+                        false,
+                    )
+                    .await;
+            }
+        }
+    }
+
     /// Apply colour changes, like saturation, hue, contrast, etc.
     //
     // TODO: consider including this in the final compositing layer, just for the performance
     // gain of not having to iterate over every cell again.
-    async fn colour_grade(&mut self) -> Result<()> {
-        let config = self.state.config.read().await;
-
-        let saturation: f64 = config.color.saturation.into();
-        let light: f64 = config.color.brightness.into();
-        let hue: f64 = config.color.hue.into();
-        drop(config);
-
-        for line in &mut self.frame.screen_cells().iter_mut() {
-            for cell in line.iter_mut() {
+    async fn colour_grade(&mut self, damage: Rect) -> Result<()> {
+        let color = self.state.config.read().await.color.clone();
+
+        for line in self
+            .frame
+            .screen_cells()
+            .iter_mut()
+            .skip(damage.y)
+            .take(damage.height)
+        {
+            for cell in line.iter_mut().skip(damage.x).take(damage.width) {
                 let foreground = cell.attrs().foreground();
-                if let Some(mut gradable) = crate::blender::Blender::extract_colour(foreground) {
-                    gradable = gradable.saturate(saturation);
-                    gradable = gradable.lighten(light);
-                    gradable = gradable.adjust_hue_fixed(hue);
+                if let Some(gradable) = crate::blender::Blender::extract_colour(foreground) {
+                    let graded = crate::colour_grading::grade(gradable, &color, self.lut.as_ref());
                     cell.attrs_mut().set_foreground(
-                        termwiz::color::ColorAttribute::TrueColorWithDefaultFallback(gradable),
+                        termwiz::color::ColorAttribute::TrueColorWithDefaultFallback(graded),
                     );
                 }
 
                 let background = cell.attrs().background();
-                if let Some(mut gradable) = crate::blender::Blender::extract_colour(background) {
-                    gradable = gradable.saturate(saturation);
-                    gradable = gradable.lighten(light);
-                    gradable = gradable.adjust_hue_fixed(hue);
+                if let Some(gradable) = crate::blender::Blender::extract_colour(background) {
+                    let graded = crate::colour_grading::grade(gradable, &color, self.lut.as_ref());
                     cell.attrs_mut().set_background(
-                        termwiz::color::ColorAttribute::TrueColorWithDefaultFallback(gradable),
+                        termwiz::color::ColorAttribute::TrueColorWithDefaultFallback(graded),
                     );
                 }
             }
@@ -665,3 +928,75 @@ impl Renderer {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn throttling() {
+        assert!((Renderer::next_throttle(1.0, 0.1) - 1.0).abs() < f32::EPSILON);
+        assert!((Renderer::next_throttle(1.0, 1.0) - 2.0).abs() < f32::EPSILON);
+        assert!((Renderer::next_throttle(4.0, 0.25) - 2.0).abs() < f32::EPSILON);
+        assert!((Renderer::next_throttle(8.0, 1.0) - MAX_THROTTLE).abs() < f32::EPSILON);
+    }
+
+    fn frame_cells(renderer: &mut Renderer) -> Vec<Vec<termwiz::cell::Cell>> {
+        renderer
+            .frame
+            .screen_cells()
+            .iter()
+            .map(|line| line.to_vec())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn recompositing_only_damaged_cells_matches_a_full_recomposite() {
+        let (protocol_tx, _) = tokio::sync::broadcast::channel(1024);
+        let state = SharedState::init(4, 2, protocol_tx).await.unwrap();
+        state.config.write().await.show_tattoy_indicator = false;
+        *state.is_rendering_enabled.write().await = true;
+        let mut renderer = Renderer {
+            is_cursor_visible: false,
+            ..Renderer::new(state, false).await.unwrap()
+        };
+
+        let mut below = crate::surface::Surface::new("below".into(), 4, 2, -1, 1.0);
+        below.add_text(0, 0, "abcd".into(), Some((0.0, 0.0, 1.0, 1.0)), None);
+        renderer
+            .render(0, FrameUpdate::TattoySurface(below))
+            .await
+            .unwrap();
+
+        let mut above = crate::surface::Surface::new("above".into(), 4, 2, 1, 0.5);
+        above.add_text(0, 1, "efgh".into(), Some((1.0, 0.0, 0.0, 1.0)), None);
+        renderer
+            .render(0, FrameUpdate::TattoySurface(above.clone()))
+            .await
+            .unwrap();
+
+        let previous = above.clone();
+        above.add_text(1, 0, "x".into(), Some((0.0, 1.0, 0.0, 1.0)), None);
+        above.damage = above.damage_since(&previous);
+        assert_eq!(
+            above.damage,
+            crate::surface::Damage::Partial(Rect::new(1, 0, 1, 1))
+        );
+        renderer
+            .render(0, FrameUpdate::TattoySurface(above))
+            .await
+            .unwrap();
+        let partially_recomposited = frame_cells(&mut renderer);
+
+        renderer.damage_everything();
+        renderer.composite().await.unwrap();
+        let fully_recomposited = frame_cells(&mut renderer);
+
+        assert_eq!(partially_recomposited, fully_recomposited);
+        let changed_cell = partially_recomposited
+            .first()
+            .and_then(|line| line.get(1))
+            .map(termwiz::cell::Cell::str);
+        assert_eq!(changed_cell, Some("x"));
+    }
+}
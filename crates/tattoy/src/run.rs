@@ -19,7 +19,8 @@ use crate::shared_state::SharedState;
 /// There a are 2 "screens" or "surfaces" to manage in Tattoy. The fancy special affects screen
 /// and the traditional PTY.
 pub(crate) enum FrameUpdate {
-    /// A frame of a tattoy TTY screen
+    /// A frame of a tattoy TTY screen, including which of its cells have changed since its
+    /// previous frame
     TattoySurface(crate::surface::Surface),
     /// A frame of a PTY terminal has been updated in the shared state
     PTYSurface,
@@ -41,6 +42,9 @@ pub(crate) enum Protocol {
     },
     /// Parsed input from STDIN.
     Input(crate::raw_input::ParsedInput),
+    /// Bytes to send directly to the PTY, bypassing Tattoy's own input handling. For example,
+    /// input injected by plugins.
+    PTYInput(Vec<u8>),
     /// The visibility of the end user's cursor.
     CursorVisibility(bool),
     /// Tattoy's configuration.
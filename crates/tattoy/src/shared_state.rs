@@ -37,6 +37,11 @@ pub(crate) struct SharedState {
     pub config: tokio::sync::RwLock<crate::config::main::Config>,
     /// All the user-configured keybindings.
     pub keybindings: tokio::sync::RwLock<crate::config::input::KeybindingsAsEvents>,
+    /// All the keybindings claimed by plugins. Matching key events are only sent to the claiming
+    /// plugin.
+    pub plugin_keybindings: tokio::sync::RwLock<crate::config::input::PluginKeybindings>,
+    /// The most recent STDERR output of every plugin.
+    pub plugin_logs: tokio::sync::RwLock<crate::tattoys::plugin_log::Logs>,
     /// Just the size of the user's terminal. All the tattoys and shadow TTY should follow this
     pub tty_size: tokio::sync::RwLock<TTYSize>,
     /// This is a view onto the active screen of the shadow terminal. It's what you would see if
@@ -59,10 +64,15 @@ pub(crate) struct SharedState {
     /// A counter for every change to the underlying PTY output. Useful for triggering behaviour on
     /// screen state changes.
     pub pty_sequence: tokio::sync::RwLock<usize>,
+    /// The shell's current working directory, if it has reported it with the OSC 7 escape sequence.
+    pub working_directory: tokio::sync::RwLock<Option<String>>,
     /// Is the application logging?
     pub is_logging: tokio::sync::RwLock<bool>,
     /// Is Tattoy rendering anything to the terminal?
     pub is_rendering_enabled: tokio::sync::RwLock<bool>,
+    /// How much to slow down animations by because the renderer is struggling to keep up. 1.0
+    /// means that animations run at their normal frame rate.
+    pub throttle: tokio::sync::RwLock<f32>,
     // TODO: I tried adding the whole palette here, but it wasn't straightforward so I've just put
     // the background for now.
     //
@@ -85,14 +95,18 @@ impl SharedState {
             main_config_file: RwLock::default(),
             config: RwLock::default(),
             keybindings: RwLock::default(),
+            plugin_keybindings: RwLock::default(),
+            plugin_logs: RwLock::default(),
             tty_size: RwLock::new(TTYSize { width, height }),
             shadow_tty_screen: RwLock::default(),
             shadow_tty_scrollback: RwLock::default(),
             is_scrolling: RwLock::default(),
             is_alternate_screen: RwLock::default(),
             pty_sequence: RwLock::default(),
+            working_directory: RwLock::default(),
             is_logging: RwLock::default(),
             is_rendering_enabled: RwLock::new(true),
+            throttle: RwLock::new(1.0),
             default_background: RwLock::default(),
         };
 
@@ -154,6 +168,18 @@ impl SharedState {
         *tty_size = TTYSize { width, height };
     }
 
+    /// Get a read lock and return the name of the plugin that has claimed the given key event, if
+    /// any.
+    pub async fn get_plugin_keybinding_owner(
+        &self,
+        key_event: &termwiz::input::KeyEvent,
+    ) -> Option<String> {
+        let plugin_keybindings = self.plugin_keybindings.read().await;
+        plugin_keybindings
+            .iter()
+            .find_map(|(plugin, bindings)| bindings.contains(key_event).then_some(plugin.clone()))
+    }
+
     /// Get a read lock and return whether the user is currently scrolling.
     pub async fn get_is_scrolling(&self) -> bool {
         let is_scrolling = self.is_scrolling.read().await;
@@ -177,4 +203,16 @@ impl SharedState {
         let mut is_alternate_screen = self.is_alternate_screen.write().await;
         *is_alternate_screen = value;
     }
+
+    /// Get a read lock and return the shell's current working directory.
+    pub async fn get_working_directory(&self) -> Option<String> {
+        let working_directory = self.working_directory.read().await;
+        working_directory.clone()
+    }
+
+    /// Get a write lock and set the shell's current working directory.
+    pub async fn set_working_directory(&self, directory: String) {
+        let mut working_directory = self.working_directory.write().await;
+        *working_directory = Some(directory);
+    }
 }
@@ -20,6 +20,176 @@ pub const BLACK: Colour = (0.0, 0.0, 0.0, 1.0);
 /// A default pure red.
 pub const RED: Colour = (1.0, 0.0, 0.0, 1.0);
 
+/// The corner of the terminal that a region is positioned relative to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum Anchor {
+    /// The top-left corner.
+    #[default]
+    TopLeft,
+    /// The top-right corner.
+    TopRight,
+    /// The bottom-left corner.
+    BottomLeft,
+    /// The bottom-right corner.
+    BottomRight,
+}
+
+/// Where to place a surface that is smaller than the terminal, like a status bar or a badge.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Region {
+    /// The corner of the terminal that the surface is positioned relative to.
+    pub anchor: Anchor,
+    /// The number of columns and rows between the anchored corner of the terminal and the same
+    /// corner of the surface.
+    pub offset: (usize, usize),
+}
+
+impl Region {
+    /// The position in the terminal of the top-left cell of a surface of the given size. Any part
+    /// of the surface that doesn't fit in the terminal is clipped by the compositor.
+    #[must_use]
+    pub const fn origin(&self, size: (usize, usize), frame_size: (usize, usize)) -> (usize, usize) {
+        let right = frame_size.0.saturating_sub(size.0 + self.offset.0);
+        let bottom = frame_size.1.saturating_sub(size.1 + self.offset.1);
+        match self.anchor {
+            Anchor::TopLeft => self.offset,
+            Anchor::TopRight => (right, self.offset.1),
+            Anchor::BottomLeft => (self.offset.0, bottom),
+            Anchor::BottomRight => (right, bottom),
+        }
+    }
+}
+
+/// A rectangle of cells. Used to track which parts of the terminal need recompositing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Rect {
+    /// The column of the rectangle's left edge.
+    pub x: usize,
+    /// The row of the rectangle's top edge.
+    pub y: usize,
+    /// The number of columns.
+    pub width: usize,
+    /// The number of rows.
+    pub height: usize,
+}
+
+impl Rect {
+    /// Instantiate
+    #[must_use]
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Does the rectangle not cover any cells?
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Is the cell inside the rectangle?
+    #[must_use]
+    pub const fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    /// The smallest rectangle that covers both rectangles.
+    #[must_use]
+    pub fn union(self, other: Self) -> Self {
+        if self.is_empty() {
+            return other;
+        }
+        if other.is_empty() {
+            return self;
+        }
+
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Self::new(x, y, right - x, bottom - y)
+    }
+
+    /// Remove any part of the rectangle that falls outside a frame of the given size.
+    #[must_use]
+    pub fn clip(self, width: usize, height: usize) -> Self {
+        let right = (self.x + self.width).min(width);
+        let bottom = (self.y + self.height).min(height);
+        Self::new(
+            self.x,
+            self.y,
+            right.saturating_sub(self.x),
+            bottom.saturating_sub(self.y),
+        )
+    }
+}
+
+/// Which cells of a surface have changed since the previous frame of the same tattoy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum Damage {
+    /// Any of the cells may have changed. This is always safe to use.
+    #[default]
+    Full,
+    /// Only the cells inside the rectangle have changed.
+    Partial(Rect),
+    /// None of the cells have changed.
+    Unchanged,
+}
+
+impl Damage {
+    /// Compare the cells of two frames of the same size. Frames of different sizes are always
+    /// fully damaged.
+    #[must_use]
+    pub fn between(
+        previous: &termwiz::surface::Surface,
+        current: &termwiz::surface::Surface,
+    ) -> Self {
+        if previous.dimensions() != current.dimensions() {
+            return Self::Full;
+        }
+
+        let mut changed = Rect::default();
+        let previous_cells = previous.get_screen_cells();
+        let current_cells = current.get_screen_cells();
+        for (y, (previous_line, current_line)) in
+            previous_cells.iter().zip(&current_cells).enumerate()
+        {
+            if previous_line == current_line {
+                continue;
+            }
+            for (x, (previous_cell, current_cell)) in
+                previous_line.iter().zip(current_line.iter()).enumerate()
+            {
+                if previous_cell != current_cell {
+                    changed = changed.union(Rect::new(x, y, 1, 1));
+                }
+            }
+        }
+
+        if changed.is_empty() {
+            Self::Unchanged
+        } else {
+            Self::Partial(changed)
+        }
+    }
+
+    /// The damaged cells of a surface that covers the given area of the frame.
+    #[must_use]
+    pub const fn within(self, area: Rect) -> Rect {
+        match self {
+            Self::Full => area,
+            Self::Partial(rect) => {
+                Rect::new(area.x + rect.x, area.y + rect.y, rect.width, rect.height)
+            }
+            Self::Unchanged => Rect::new(area.x, area.y, 0, 0),
+        }
+    }
+}
+
 /// `Surface`
 #[derive(Clone)]
 pub(crate) struct Surface {
@@ -35,6 +205,14 @@ pub(crate) struct Surface {
     pub layer: i16,
     /// The transparency of the surface.
     pub opacity: f32,
+    /// How the surface's colours are combined with the layers below it.
+    pub blend_mode: crate::blender::BlendMode,
+    /// Where to place the surface when it only covers part of the terminal. `None` means that the
+    /// surface covers the whole terminal.
+    pub region: Option<Region>,
+    /// The cells that have changed since the tattoy's previous frame. The renderer only
+    /// recomposites the parts of the terminal that have changed.
+    pub damage: Damage,
     /// A surface of terminal cells
     pub surface: termwiz::surface::Surface,
 }
@@ -49,10 +227,44 @@ impl Surface {
             height,
             layer,
             opacity,
+            blend_mode: crate::blender::BlendMode::Normal,
+            region: None,
+            damage: Damage::Full,
             surface: termwiz::surface::Surface::new(width, height),
         }
     }
 
+    /// Work out which cells have changed since the previous frame of the same tattoy. Changing
+    /// how or where the surface is composited damages all of it.
+    #[must_use]
+    pub fn damage_since(&self, previous: &Self) -> Damage {
+        let is_placement_changed = self.width != previous.width
+            || self.height != previous.height
+            || self.layer != previous.layer
+            || self.opacity.to_bits() != previous.opacity.to_bits()
+            || self.blend_mode != previous.blend_mode
+            || self.region != previous.region;
+        if is_placement_changed {
+            return Damage::Full;
+        }
+
+        Damage::between(&previous.surface, &self.surface)
+    }
+
+    /// The part of a frame of the given size that the surface is composited onto.
+    #[must_use]
+    pub fn area(&self, frame_size: (usize, usize)) -> Rect {
+        let size = self.surface.dimensions();
+        let area = match self.region {
+            Some(region) => {
+                let (x, y) = region.origin(size, frame_size);
+                Rect::new(x, y, size.0, size.1)
+            }
+            None => Rect::new(0, 0, frame_size.0, frame_size.1),
+        };
+        area.clip(frame_size.0, frame_size.1)
+    }
+
     /// Add a pixel ("▀", "▄") to a tattoy surface.
     ///
     /// The rule is that we default to rendering any pair of colours using the upper half block.
@@ -121,6 +333,55 @@ impl Surface {
         Ok(())
     }
 
+    /// Add a dense RGBA image to a tattoy surface, using the same half-block pixels as
+    /// `add_pixel()`. The `origin` is in pixel coordinates. Fully transparent pixels and any
+    /// pixels that fall outside the surface are skipped.
+    pub fn add_image(
+        &mut self,
+        origin: (usize, usize),
+        width: usize,
+        height: usize,
+        pixels: &[u8],
+    ) -> Result<()> {
+        let expected_length = width
+            .checked_mul(height)
+            .and_then(|area| area.checked_mul(4))
+            .context("Image dimensions are too big")?;
+        if pixels.len() != expected_length {
+            bail!(
+                "Image of {width}x{height} should have {expected_length} bytes, but has {}",
+                pixels.len()
+            );
+        }
+
+        let max_x = self.width;
+        let max_y = self.height * 2;
+        for (index, rgba) in pixels.chunks_exact(4).enumerate() {
+            let &[red, green, blue, alpha] = rgba else {
+                continue;
+            };
+            if alpha == 0 {
+                continue;
+            }
+
+            let x = origin.0 + index.rem_euclid(width);
+            let y = origin.1 + index.div_euclid(width);
+            if x >= max_x || y >= max_y {
+                continue;
+            }
+
+            let colour = (
+                f32::from(red) / 255.0,
+                f32::from(green) / 255.0,
+                f32::from(blue) / 255.0,
+                f32::from(alpha) / 255.0,
+            );
+            self.add_pixel(x, y, colour)?;
+        }
+
+        Ok(())
+    }
+
     /// Overlay text at a given coord with the given colours.
     pub fn add_text(
         &mut self,
@@ -149,6 +410,60 @@ impl Surface {
         self.surface.add_change(text);
     }
 
+    /// Overlay text at a given coord with the given colours and styling. The styling only applies
+    /// to this text, it doesn't leak into any text that is added afterwards.
+    pub fn add_styled_text(
+        &mut self,
+        x: usize,
+        y: usize,
+        text: String,
+        maybe_background_colour: Option<Colour>,
+        maybe_foreground_colour: Option<Colour>,
+        maybe_attributes: Option<&tattoy_protocol::TextAttributes>,
+    ) {
+        let Some(attributes) = maybe_attributes else {
+            self.add_text(x, y, text, maybe_background_colour, maybe_foreground_colour);
+            return;
+        };
+
+        self.surface
+            .add_changes(Self::make_text_attribute_changes(attributes));
+        self.add_text(x, y, text, maybe_background_colour, maybe_foreground_colour);
+        self.surface.add_changes(Self::make_text_attribute_changes(
+            &tattoy_protocol::TextAttributes::default(),
+        ));
+    }
+
+    /// Make the Termwiz changes that style text, like making it bold or italic.
+    fn make_text_attribute_changes(
+        attributes: &tattoy_protocol::TextAttributes,
+    ) -> Vec<TermwizChange> {
+        let intensity = if attributes.bold {
+            termwiz::cell::Intensity::Bold
+        } else {
+            termwiz::cell::Intensity::Normal
+        };
+        let underline = if attributes.underline {
+            termwiz::cell::Underline::Single
+        } else {
+            termwiz::cell::Underline::None
+        };
+        let hyperlink = attributes
+            .hyperlink
+            .as_ref()
+            .map(|url| std::sync::Arc::new(termwiz::hyperlink::Hyperlink::new(url.clone())));
+
+        vec![
+            TermwizChange::Attribute(termwiz::cell::AttributeChange::Intensity(intensity)),
+            TermwizChange::Attribute(termwiz::cell::AttributeChange::Italic(attributes.italic)),
+            TermwizChange::Attribute(termwiz::cell::AttributeChange::Underline(underline)),
+            TermwizChange::Attribute(termwiz::cell::AttributeChange::StrikeThrough(
+                attributes.strikethrough,
+            )),
+            TermwizChange::Attribute(termwiz::cell::AttributeChange::Hyperlink(hyperlink)),
+        ]
+    }
+
     /// Make a Termwiz colour attribute
     #[must_use]
     pub const fn make_colour_attribute(colour: Colour) -> termwiz::color::ColorAttribute {
@@ -252,6 +567,43 @@ mod test {
         );
     }
 
+    #[test]
+    fn add_image() {
+        let mut surface = Surface::new("test".into(), 2, 1, -1, 1.0);
+
+        #[rustfmt::skip]
+        let pixels = [
+            255, 255, 255, 255,    0, 0, 0, 0,
+            255, 0,   0,   255,    0, 0, 0, 0,
+            255, 255, 255, 255,    255, 255, 255, 255,
+        ];
+        surface.add_image((0, 0), 2, 3, &pixels).unwrap();
+
+        let cell = &surface.surface.screen_cells()[0][0];
+        assert_eq!(cell.str(), "▀");
+        assert_eq!(
+            cell.attrs().foreground(),
+            Surface::make_colour_attribute(WHITE)
+        );
+        assert_eq!(
+            cell.attrs().background(),
+            Surface::make_colour_attribute(RED)
+        );
+
+        let cell = &surface.surface.screen_cells()[0][1];
+        assert_eq!(cell.str(), " ");
+    }
+
+    #[test]
+    fn add_image_with_wrong_size() {
+        let mut surface = Surface::new("test".into(), 2, 1, -1, 1.0);
+        let result = surface.add_image((0, 0), 2, 2, &[0; 4]).unwrap_err();
+        assert_eq!(
+            format!("{}", result.root_cause()),
+            "Image of 2x2 should have 16 bytes, but has 4"
+        );
+    }
+
     #[test]
     fn add_pixel_at_bottom_of_empty_cell() {
         let mut surface = Surface::new("test".into(), 1, 1, -1, 1.0);
@@ -327,4 +679,91 @@ mod test {
         assert_eq!(first_cell.attrs().foreground(), fg);
         assert_eq!(first_cell.attrs().background(), bg);
     }
+
+    #[test]
+    fn styled_text_only_styles_itself() {
+        let mut surface = Surface::new("test".into(), 2, 1, -1, 1.0);
+        let attributes = tattoy_protocol::TextAttributes::builder()
+            .bold(true)
+            .italic(true)
+            .underline(true)
+            .strikethrough(true)
+            .hyperlink("https://tattoy.sh".to_owned())
+            .build();
+
+        surface.add_styled_text(0, 0, "a".into(), None, None, Some(&attributes));
+        surface.add_text(1, 0, "b".into(), None, None);
+
+        let styled = surface.surface.screen_cells()[0][0].attrs().clone();
+        assert_eq!(styled.intensity(), termwiz::cell::Intensity::Bold);
+        assert!(styled.italic());
+        assert_eq!(styled.underline(), termwiz::cell::Underline::Single);
+        assert!(styled.strikethrough());
+        assert_eq!(styled.hyperlink().unwrap().uri(), "https://tattoy.sh");
+
+        let plain = surface.surface.screen_cells()[0][1].attrs().clone();
+        assert_eq!(plain.intensity(), termwiz::cell::Intensity::Normal);
+        assert!(!plain.italic());
+        assert_eq!(plain.underline(), termwiz::cell::Underline::None);
+        assert!(!plain.strikethrough());
+        assert!(plain.hyperlink().is_none());
+    }
+
+    #[test]
+    fn region_origins() {
+        let region = |anchor| Region {
+            anchor,
+            offset: (1, 2),
+        };
+        let size = (3, 2);
+        let frame_size = (10, 5);
+
+        assert_eq!(region(Anchor::TopLeft).origin(size, frame_size), (1, 2));
+        assert_eq!(region(Anchor::TopRight).origin(size, frame_size), (6, 2));
+        assert_eq!(region(Anchor::BottomLeft).origin(size, frame_size), (1, 1));
+        assert_eq!(region(Anchor::BottomRight).origin(size, frame_size), (6, 1));
+
+        // The terminal has shrunk to be smaller than the region.
+        assert_eq!(region(Anchor::BottomRight).origin(size, (2, 2)), (0, 0));
+    }
+
+    #[test]
+    fn rect_union_and_clip() {
+        let rect = Rect::new(1, 1, 2, 2);
+
+        assert_eq!(rect.union(Rect::default()), rect);
+        assert_eq!(rect.union(Rect::new(4, 0, 1, 1)), Rect::new(1, 0, 4, 3));
+        assert_eq!(rect.clip(2, 5), Rect::new(1, 1, 1, 2));
+        assert!(rect.clip(1, 1).is_empty());
+        assert!(rect.contains(2, 2));
+        assert!(!rect.contains(3, 1));
+    }
+
+    #[test]
+    fn damage_since_previous_frame() {
+        let previous = Surface::new("test".into(), 4, 3, 1, 1.0);
+        let mut current = previous.clone();
+        assert_eq!(current.damage_since(&previous), Damage::Unchanged);
+
+        current.add_text(1, 1, "ab".into(), None, None);
+        assert_eq!(
+            current.damage_since(&previous),
+            Damage::Partial(Rect::new(1, 1, 2, 1))
+        );
+
+        current.opacity = 0.5;
+        assert_eq!(current.damage_since(&previous), Damage::Full);
+    }
+
+    #[test]
+    fn damage_within_an_area() {
+        let area = Rect::new(5, 2, 4, 3);
+
+        assert_eq!(Damage::Full.within(area), area);
+        assert_eq!(
+            Damage::Partial(Rect::new(1, 1, 2, 1)).within(area),
+            Rect::new(6, 3, 2, 1)
+        );
+        assert!(Damage::Unchanged.within(area).is_empty());
+    }
 }
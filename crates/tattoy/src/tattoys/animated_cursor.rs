@@ -23,6 +23,8 @@ pub(crate) struct Config {
     pub opacity: f32,
     /// The scale of the cursor.
     pub cursor_scale: f32,
+    /// The cursor animation's frame rate. Defaults to the global frame rate.
+    pub frame_rate: Option<u32>,
 }
 
 impl Default for Config {
@@ -37,6 +39,7 @@ impl Default for Config {
             .into(),
             opacity: 0.75,
             cursor_scale: 1.0,
+            frame_rate: None,
         }
     }
 }
@@ -14,6 +14,8 @@ pub(crate) struct Config {
     pub opacity: f32,
     /// The layer of the compositor on which the command output is rendered.
     pub layer: i16,
+    /// How the command output's colours are combined with the layers below it.
+    pub blend_mode: crate::blender::BlendMode,
     /// The command to run.
     command: Vec<String>,
     /// Whether the command is expected to exit or not.
@@ -26,6 +28,7 @@ impl Default for Config {
             enabled: false,
             opacity: 0.75,
             layer: -8,
+            blend_mode: crate::blender::BlendMode::Normal,
             command: vec!["echo".to_owned(), "No command provided".to_owned()],
             expect_exit: false,
         }
@@ -133,6 +136,7 @@ impl BGCommand {
         self.palette.convert_cells_to_true_colour(&mut output);
         self.tattoy.opacity = self.tattoy.state.config.read().await.bg_command.opacity;
         self.tattoy.layer = self.tattoy.state.config.read().await.bg_command.layer;
+        self.tattoy.blend_mode = self.tattoy.state.config.read().await.bg_command.blend_mode;
 
         #[expect(
             clippy::collapsible_match,
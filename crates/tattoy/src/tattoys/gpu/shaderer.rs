@@ -47,6 +47,11 @@ pub(crate) trait Shaderer: Sized {
     /// Get the current configured opacity for the tattoy.
     async fn get_opacity(&self) -> f32;
 
+    /// Get the current configured blend mode for the tattoy.
+    async fn get_blend_mode(&self) -> crate::blender::BlendMode {
+        crate::blender::BlendMode::Normal
+    }
+
     /// A wrapper for the render step.
     async fn render_handler(&mut self) -> Result<()> {
         self.render().await
@@ -228,6 +233,7 @@ pub(crate) trait Shaderer: Sized {
 
         self.update_cursor().await?;
 
+        self.tattoy_mut().blend_mode = self.get_blend_mode().await;
         self.tattoy_mut().initialise_surface();
         self.tattoy_mut().opacity = self.get_opacity().await;
         self.tattoy_mut().layer = self.get_layer().await;
@@ -18,6 +18,10 @@ pub(crate) struct Config {
     max_width: u16,
     /// The speed of the minimap show/hide animation.
     animation_speed: f32,
+    /// How the minimap's colours are combined with the layers below it.
+    blend_mode: crate::blender::BlendMode,
+    /// The frame rate of the show/hide animation. Defaults to the global frame rate.
+    pub frame_rate: Option<u32>,
 }
 
 impl Default for Config {
@@ -26,6 +30,8 @@ impl Default for Config {
             enabled: true,
             max_width: 15,
             animation_speed: 0.15,
+            blend_mode: crate::blender::BlendMode::Normal,
+            frame_rate: None,
         }
     }
 }
@@ -232,6 +238,7 @@ impl Minimap {
 
         tracing::trace!("Rendering minimap.");
 
+        self.tattoy.blend_mode = self.state.config.read().await.minimap.blend_mode;
         self.tattoy.initialise_surface();
 
         let dimensions = self.scrollback.dimensions();
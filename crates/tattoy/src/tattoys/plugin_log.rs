@@ -0,0 +1,240 @@
+//! An overlay showing the most recent STDERR output of a plugin. Useful for debugging plugins.
+
+use color_eyre::eyre::Result;
+
+/// The maximum number of STDERR lines kept for each plugin.
+pub(crate) const MAX_LINES: usize = 200;
+
+/// The height of the overlay, including its title row.
+const HEIGHT: u16 = 12;
+
+/// How often the overlay is redrawn whilst it's visible, so that new log lines appear.
+const REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// The colour of the overlay's text.
+const TEXT_COLOUR: crate::surface::Colour = (1.0, 1.0, 1.0, 1.0);
+
+/// The background colour of the overlay's title.
+const TITLE_BACKGROUND: crate::surface::Colour = (0.25, 0.25, 0.25, 0.95);
+
+/// The background colour of the log lines.
+const LOG_BACKGROUND: crate::surface::Colour = (0.0, 0.0, 0.0, 0.85);
+
+/// The most recent STDERR lines of every plugin, keyed by the plugin's name.
+pub(crate) type Logs = std::collections::HashMap<String, std::collections::VecDeque<String>>;
+
+/// Record a line of a plugin's STDERR, forgetting the oldest line if there are too many.
+pub(crate) fn add_line(lines: &mut std::collections::VecDeque<String>, line: String) {
+    if lines.len() >= MAX_LINES {
+        lines.pop_front();
+    }
+    lines.push_back(line);
+}
+
+/// `PluginLog`
+pub(crate) struct PluginLog {
+    /// The base Tattoy struct
+    tattoy: super::tattoyer::Tattoyer,
+    /// The name of the plugin whose log is being shown. `None` when the overlay is hidden.
+    selected: Option<String>,
+}
+
+impl PluginLog {
+    /// Instantiate
+    async fn new(
+        output_channel: tokio::sync::mpsc::Sender<crate::run::FrameUpdate>,
+        state: std::sync::Arc<crate::shared_state::SharedState>,
+    ) -> Self {
+        let tattoy = super::tattoyer::Tattoyer::new(
+            "plugin_log".to_owned(),
+            state,
+            150,
+            1.0,
+            output_channel,
+        )
+        .await;
+        Self {
+            tattoy,
+            selected: None,
+        }
+    }
+
+    /// Our main entrypoint.
+    pub(crate) async fn start(
+        output: tokio::sync::mpsc::Sender<crate::run::FrameUpdate>,
+        state: std::sync::Arc<crate::shared_state::SharedState>,
+    ) -> Result<()> {
+        let mut protocol = state.protocol_tx.subscribe();
+        let mut plugin_log = Self::new(output, state).await;
+        let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+
+        #[expect(
+            clippy::integer_division_remainder_used,
+            reason = "This is caused by the `tokio::select!`"
+        )]
+        loop {
+            tokio::select! {
+                _ = refresh.tick(), if plugin_log.selected.is_some() => {
+                    plugin_log.render().await?;
+                },
+                result = protocol.recv() => {
+                    if matches!(result, Ok(crate::run::Protocol::End)) {
+                        break;
+                    }
+                    plugin_log.handle_protocol_message(result).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle messages from the main Tattoy app.
+    async fn handle_protocol_message(
+        &mut self,
+        result: std::result::Result<crate::run::Protocol, tokio::sync::broadcast::error::RecvError>,
+    ) -> Result<()> {
+        match result {
+            Ok(message) => {
+                let is_toggle = matches!(
+                    message,
+                    crate::run::Protocol::KeybindEvent(
+                        crate::config::input::KeybindingAction::TogglePluginLog
+                    )
+                );
+                self.tattoy.handle_common_protocol_messages(message)?;
+
+                if is_toggle {
+                    let names: Vec<String> = self
+                        .tattoy
+                        .state
+                        .config
+                        .read()
+                        .await
+                        .plugins
+                        .iter()
+                        .map(|plugin| plugin.name().to_owned())
+                        .collect();
+                    self.selected = Self::next_plugin(&names, self.selected.as_deref());
+                    self.render().await?;
+                }
+            }
+            Err(error) => tracing::error!("Receiving protocol message: {error:?}"),
+        }
+
+        Ok(())
+    }
+
+    /// The plugin to show after the current one. The overlay cycles through each plugin in the
+    /// order of the config, and is then hidden again.
+    fn next_plugin(names: &[String], current: Option<&str>) -> Option<String> {
+        let next_index = match current {
+            Some(current_name) => names.iter().position(|name| name == current_name)? + 1,
+            None => 0,
+        };
+        names.get(next_index).cloned()
+    }
+
+    /// Tick the render
+    async fn render(&mut self) -> Result<()> {
+        let Some(name) = self.selected.clone() else {
+            return self.tattoy.send_blank_output().await;
+        };
+
+        let height = HEIGHT.min(self.tattoy.height);
+        if height == 0 {
+            return Ok(());
+        }
+        let log_height = usize::from(height - 1);
+        let mut lines: Vec<String> = self
+            .tattoy
+            .state
+            .plugin_logs
+            .read()
+            .await
+            .get(&name)
+            .map(|log| log.iter().rev().take(log_height).rev().cloned().collect())
+            .unwrap_or_default();
+        if lines.is_empty() {
+            lines.push("No STDERR output yet.".to_owned());
+        }
+
+        self.tattoy.initialise_region_surface(
+            self.tattoy.width,
+            height,
+            crate::surface::Region {
+                anchor: crate::surface::Anchor::BottomLeft,
+                offset: (0, 0),
+            },
+        );
+        let width = usize::from(self.tattoy.width);
+
+        let title = format!(" '{name}' plugin STDERR ");
+        self.tattoy.surface.add_text(
+            0,
+            0,
+            Self::fit_to_width(&title, width),
+            Some(TITLE_BACKGROUND),
+            Some(TEXT_COLOUR),
+        );
+        for row in 0..log_height {
+            let line = lines.get(row).map_or("", String::as_str);
+            self.tattoy.surface.add_text(
+                0,
+                row + 1,
+                Self::fit_to_width(line, width),
+                Some(LOG_BACKGROUND),
+                Some(TEXT_COLOUR),
+            );
+        }
+
+        self.tattoy.send_output().await
+    }
+
+    /// Truncate or pad the text so that it exactly fills a row. Control characters, like tabs,
+    /// are removed because they would mess up the layout.
+    fn fit_to_width(text: &str, width: usize) -> String {
+        let mut fitted: String = text
+            .chars()
+            .filter(|character| !character.is_control())
+            .take(width)
+            .collect();
+        let length = fitted.chars().count();
+        fitted.extend(std::iter::repeat_n(' ', width.saturating_sub(length)));
+        fitted
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cycling_through_plugins() {
+        let names = vec!["first".to_owned(), "second".to_owned()];
+
+        let first = PluginLog::next_plugin(&names, None);
+        assert_eq!(first.as_deref(), Some("first"));
+        let second = PluginLog::next_plugin(&names, first.as_deref());
+        assert_eq!(second.as_deref(), Some("second"));
+        assert_eq!(PluginLog::next_plugin(&names, second.as_deref()), None);
+        assert_eq!(PluginLog::next_plugin(&names, Some("removed")), None);
+    }
+
+    #[test]
+    fn only_the_most_recent_lines_are_kept() {
+        let mut lines = std::collections::VecDeque::new();
+        for number in 0..=MAX_LINES {
+            add_line(&mut lines, number.to_string());
+        }
+
+        assert_eq!(lines.len(), MAX_LINES);
+        assert_eq!(lines.front().unwrap(), "1");
+    }
+
+    #[test]
+    fn fitting_text_to_width() {
+        assert_eq!(PluginLog::fit_to_width("a\tb", 4), "ab  ");
+        assert_eq!(PluginLog::fit_to_width("abcdef", 4), "abcd");
+    }
+}
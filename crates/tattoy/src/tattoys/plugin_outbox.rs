@@ -0,0 +1,196 @@
+//! A bounded queue of messages waiting to be written to a plugin. The actual writing happens on a
+//! dedicated thread, so that a plugin that stops reading its input can't block the rest of
+//! Tattoy.
+
+use color_eyre::eyre::Result;
+
+/// The maximum number of messages that can be waiting to be written to a plugin.
+const MAX_QUEUED_MESSAGES: usize = 256;
+
+/// A message that has been encoded and is ready to be written to the plugin.
+struct QueuedMessage {
+    /// The encoded message.
+    bytes: Vec<u8>,
+    /// Whether the message is a PTY update or diff. Only the most recent one is worth sending.
+    is_pty_update: bool,
+}
+
+/// The state shared between Tattoy and the writer thread.
+#[derive(Default)]
+struct State {
+    /// The messages waiting to be written.
+    queue: std::collections::VecDeque<QueuedMessage>,
+    /// When the writer thread started writing its current message. `None` if it isn't writing.
+    write_started_at: Option<std::time::Instant>,
+    /// Whether the plugin has gone away and the writer thread should stop.
+    is_closed: bool,
+}
+
+/// Messages waiting to be written to a plugin.
+#[derive(Default)]
+pub(crate) struct Outbox {
+    /// The queue and the state of the writer thread.
+    state: std::sync::Mutex<State>,
+    /// Wakes the writer thread when there's a new message.
+    condvar: std::sync::Condvar,
+}
+
+impl Outbox {
+    /// Start a thread that writes queued messages to the plugin.
+    pub(crate) fn start(
+        name: String,
+        mut writer: impl std::io::Write + Send + 'static,
+    ) -> std::sync::Arc<Self> {
+        let outbox = std::sync::Arc::new(Self::default());
+        let writer_outbox = std::sync::Arc::clone(&outbox);
+        std::thread::spawn(move || {
+            while let Some(bytes) = writer_outbox.next_message() {
+                let result = writer.write_all(&bytes).and_then(|()| writer.flush());
+                writer_outbox.finish_write();
+                if let Err(error) = result {
+                    tracing::debug!("Stopped writing to plugin '{name}': {error:?}");
+                    break;
+                }
+            }
+            writer_outbox.close();
+        });
+
+        outbox
+    }
+
+    /// Lock the shared state. A panic while holding the lock can't leave the state inconsistent,
+    /// so a poisoned lock is still usable.
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Queue a message to be written to the plugin. Queueing a PTY update replaces any PTY update
+    /// that is still waiting, so callers must only queue a PTY diff when there isn't a PTY update
+    /// waiting, see `Self::has_pending_pty_update()`.
+    pub(crate) fn push(&self, bytes: Vec<u8>, is_pty_update: bool) -> Result<()> {
+        let mut state = self.lock();
+        if state.is_closed {
+            color_eyre::eyre::bail!("The plugin is no longer reading messages");
+        }
+
+        if is_pty_update {
+            state.queue.retain(|message| !message.is_pty_update);
+        }
+        if state.queue.len() >= MAX_QUEUED_MESSAGES {
+            color_eyre::eyre::bail!(
+                "Dropped message because there are already {MAX_QUEUED_MESSAGES} messages waiting \
+                to be read by the plugin"
+            );
+        }
+
+        state.queue.push_back(QueuedMessage {
+            bytes,
+            is_pty_update,
+        });
+        drop(state);
+        self.condvar.notify_one();
+
+        Ok(())
+    }
+
+    /// Whether there's a PTY update that the plugin hasn't started reading yet.
+    pub(crate) fn has_pending_pty_update(&self) -> bool {
+        self.lock()
+            .queue
+            .iter()
+            .any(|message| message.is_pty_update)
+    }
+
+    /// How long the plugin has been failing to read the message currently being written to it.
+    pub(crate) fn stalled_for(&self) -> Option<std::time::Duration> {
+        self.lock()
+            .write_started_at
+            .map(|started_at| started_at.elapsed())
+    }
+
+    /// Stop the writer thread.
+    pub(crate) fn close(&self) {
+        let mut state = self.lock();
+        state.is_closed = true;
+        state.queue.clear();
+        drop(state);
+        self.condvar.notify_one();
+    }
+
+    /// Wait for the next message to write. Returns `None` once the outbox is closed.
+    fn next_message(&self) -> Option<Vec<u8>> {
+        let mut state = self.lock();
+        loop {
+            if state.is_closed {
+                return None;
+            }
+            if let Some(message) = state.queue.pop_front() {
+                state.write_started_at = Some(std::time::Instant::now());
+                return Some(message.bytes);
+            }
+            state = self
+                .condvar
+                .wait(state)
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+        }
+    }
+
+    /// Record that the current message has been written.
+    fn finish_write(&self) {
+        self.lock().write_started_at = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read as _;
+
+    fn queued(outbox: &Outbox) -> Vec<Vec<u8>> {
+        outbox
+            .lock()
+            .queue
+            .iter()
+            .map(|message| message.bytes.clone())
+            .collect()
+    }
+
+    #[test]
+    fn only_the_latest_pty_update_is_kept() {
+        let outbox = Outbox::default();
+        outbox.push(b"pty 1".to_vec(), true).unwrap();
+        outbox.push(b"key".to_vec(), false).unwrap();
+        assert!(outbox.has_pending_pty_update());
+        outbox.push(b"pty 2".to_vec(), true).unwrap();
+
+        assert_eq!(queued(&outbox), vec![b"key".to_vec(), b"pty 2".to_vec()]);
+    }
+
+    #[test]
+    fn full_outbox_drops_messages() {
+        let outbox = Outbox::default();
+        for _ in 0..MAX_QUEUED_MESSAGES {
+            outbox.push(b"key".to_vec(), false).unwrap();
+        }
+
+        assert!(outbox.push(b"key".to_vec(), false).is_err());
+        assert_eq!(queued(&outbox).len(), MAX_QUEUED_MESSAGES);
+    }
+
+    #[test]
+    fn messages_are_written_in_order() {
+        let (mut reader, writer) = std::io::pipe().unwrap();
+        let outbox = Outbox::start("test".to_owned(), writer);
+        outbox.push(b"a".to_vec(), false).unwrap();
+        outbox.push(b"b".to_vec(), true).unwrap();
+
+        let mut bytes = [0; 2];
+        reader.read_exact(&mut bytes).unwrap();
+        assert_eq!(&bytes, b"ab");
+
+        outbox.close();
+        assert!(outbox.push(b"c".to_vec(), false).is_err());
+    }
+}
@@ -1,54 +1,271 @@
 //! Run custom external code that gets rendered as tattoys
 
 use core::panic;
-use std::io::{Read as _, Write as _};
+use std::io::BufRead as _;
 
 use color_eyre::eyre::{ContextCompat as _, Result};
+use shadow_terminal::termwiz;
 
 /// The default compositing layer the plugin is rendered to. Can be manually set inn the config.
 const DEFAULT_LAYER: i16 = -10;
 /// The default transparency for the plugin output.
 const DEFAULT_OPACITY: f32 = 1.0;
+/// The default number of times a crashed plugin is restarted. Can be manually set in the config.
+const DEFAULT_MAX_RESTARTS: u32 = 3;
+/// How long to wait before the first restart of a crashed plugin. Every subsequent restart waits
+/// twice as long as the previous one.
+const RESTART_BACKOFF_INITIAL: std::time::Duration = std::time::Duration::from_millis(500);
+/// The longest we'll ever wait before restarting a crashed plugin.
+const RESTART_BACKOFF_MAX: std::time::Duration = std::time::Duration::from_secs(30);
+/// If a plugin has been running for at least this long before crashing, then we consider it to
+/// have been stable and so its restart count is reset.
+const STABLE_RUN_DURATION: std::time::Duration = std::time::Duration::from_secs(60);
+/// How many of the most recent plugin exits to show in restart notifications.
+const RESTART_HISTORY_LENGTH: usize = 5;
+/// The most notifications that a plugin can send within `NOTIFICATION_LIMIT_WINDOW`. Any more are
+/// dropped.
+const NOTIFICATION_LIMIT: usize = 5;
+/// The period of time over which a plugin's notifications are counted.
+const NOTIFICATION_LIMIT_WINDOW: std::time::Duration = std::time::Duration::from_secs(10);
+/// How often plugins that receive PTY diffs are sent the entire PTY screen, so that they can
+/// recover from any missed diffs.
+const PTY_RESYNC_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// The default number of seconds that a plugin can go without reading its input before it's
+/// considered unresponsive.
+const DEFAULT_RESPONSE_TIMEOUT: u64 = 10;
+/// How long a plugin has to send its capabilities after it starts. Plugins that don't were most
+/// likely built against a version of the plugin protocol from before the capabilities handshake.
+const HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+/// How often to check whether plugins have become unresponsive.
+const WATCHDOG_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// The kinds of plugin that Tattoy can run.
+#[derive(serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// An executable that runs natively on the user's machine.
+    #[default]
+    Native,
+    /// A WebAssembly module that runs in a sandbox.
+    Wasm,
+}
 
 /// User-configurable settings for the minimap
 #[derive(serde::Deserialize, Debug, Clone)]
 pub struct Config {
     /// The name of the plugin. Can be any string.
     name: String,
-    /// The path to the plugin executable.
-    path: std::path::PathBuf,
+    /// Whether the plugin is a native executable or a WebAssembly module. Defaults to native.
+    kind: Option<Kind>,
+    /// The path to the plugin executable, or WebAssembly module. Either this or `socket` must be
+    /// set.
+    path: Option<std::path::PathBuf>,
+    /// The path to the Unix socket of an already running plugin daemon. Either this or `path` must
+    /// be set.
+    socket: Option<std::path::PathBuf>,
+    /// Command line arguments for the plugin executable, or WebAssembly module.
+    args: Option<Vec<String>>,
+    /// Extra environment variables for the plugin executable, or WebAssembly module.
+    env: Option<std::collections::HashMap<String, String>>,
+    /// The working directory of the plugin executable. Defaults to Tattoy's own working directory.
+    cwd: Option<std::path::PathBuf>,
+    /// Free-form settings that are sent to the plugin in its `hello` message.
+    settings: Option<tattoy_protocol::Settings>,
     /// The layer upon which the plugin is rendered.
     layer: Option<i16>,
     /// The transparency of the plugin output.
     opacity: Option<f32>,
+    /// How the plugin's colours are combined with the layers below it. Defaults to normal.
+    blend_mode: Option<crate::blender::BlendMode>,
     /// Whether the plugin is enabled.
     pub enabled: Option<bool>,
+    /// The kinds of user input (key presses, mouse events, pastes) to send to the plugin. By
+    /// default plugins don't receive any input.
+    input: Option<Vec<tattoy_protocol::InputKind>>,
+    /// The maximum number of times the plugin is restarted after it crashes. Set to `0` to never
+    /// restart it.
+    max_restarts: Option<u32>,
+    /// The encoding of messages sent to and from the plugin. Defaults to JSON.
+    encoding: Option<tattoy_protocol::encoding::Encoding>,
+    /// Whether the plugin is allowed to send input to the user's shell, as if the user had typed
+    /// it. Defaults to `false`.
+    allow_input_injection: Option<bool>,
+    /// How many seconds the plugin can go without reading its input before the user is told that
+    /// it's unresponsive. Defaults to 10.
+    response_timeout: Option<u64>,
+    /// Whether to kill, and then restart, the plugin when it becomes unresponsive. Defaults to
+    /// `false`.
+    kill_when_unresponsive: Option<bool>,
+}
+
+impl Config {
+    /// The name of the plugin, as given in the config.
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Plugins are enabled unless the user has explicitly disabled them.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+
+    /// The enabled plugins in a reloaded config that need starting. That's the ones that weren't
+    /// enabled in the previous config, and the ones that aren't `running`, most likely because
+    /// they failed to start, but whose config now starts them differently.
+    pub(crate) fn newly_enabled<'reloaded>(
+        previous: &[Self],
+        reloaded: &'reloaded [Self],
+        running: &[&str],
+    ) -> Vec<&'reloaded Self> {
+        reloaded
+            .iter()
+            .filter(|config| config.is_enabled())
+            .filter(|config| {
+                let maybe_old = previous
+                    .iter()
+                    .find(|old| old.name == config.name && old.is_enabled());
+                match maybe_old {
+                    Some(old) => {
+                        !running.contains(&config.name.as_str()) && !old.is_started_the_same(config)
+                    }
+                    None => true,
+                }
+            })
+            .collect()
+    }
+
+    /// Whether the two configs start the plugin in the same way.
+    fn is_started_the_same(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.path == other.path
+            && self.socket == other.socket
+            && self.args == other.args
+            && self.env == other.env
+            && self.cwd == other.cwd
+    }
+}
+
+/// How Tattoy is connected to a plugin.
+enum Connection {
+    /// The plugin's executable, spawned as a subprocess.
+    Process(std::process::Child),
+    /// A long-running plugin daemon, listening on a Unix socket.
+    #[cfg(not(target_os = "windows"))]
+    Socket(std::os::unix::net::UnixStream),
+    /// A WebAssembly module, running in Tattoy's embedded sandbox.
+    Wasm(super::wasm_plugin::Instance),
+}
+
+/// The stream that Tattoy reads the plugin's messages from. Either its STDOUT or its socket.
+type PluginReader = std::io::BufReader<Box<dyn std::io::Read + Send>>;
+
+/// The stream that Tattoy writes messages to the plugin on. Either its STDIN or its socket.
+type PluginWriter = std::io::BufWriter<Box<dyn std::io::Write + Send>>;
+
+/// Everything needed to talk to a freshly started plugin. The STDERR is only available for
+/// plugins that Tattoy runs itself.
+type PluginStreams = (
+    Connection,
+    PluginReader,
+    PluginWriter,
+    Option<Box<dyn std::io::Read + Send>>,
+);
+
+/// A running instance of a plugin.
+struct PluginProcess {
+    /// The plugin's subprocess or socket.
+    connection: Connection,
+    /// Messages waiting to be sent to the plugin over its STDIN or socket.
+    outbox: std::sync::Arc<super::plugin_outbox::Outbox>,
+    /// Output stream from spawned plugin process.
+    parsed_messages_rx: tokio::sync::mpsc::Receiver<tattoy_protocol::PluginOutputMessages>,
+    /// For telling the thread that listens to the plugin's output to stop.
+    listener_tx: Option<tokio::sync::oneshot::Sender<crate::run::Protocol>>,
+    /// When the process was started.
+    started_at: tokio::time::Instant,
+    /// The encoding that the plugin was started with. It can't change whilst the plugin is
+    /// running, even if the config does, because the plugin only learns it from the `hello`
+    /// message.
+    encoding: tattoy_protocol::encoding::Encoding,
+}
+
+impl Drop for PluginProcess {
+    fn drop(&mut self) {
+        self.outbox.close();
+    }
 }
 
 /// Plugins
 pub struct Plugin {
     /// The base Tattoy struct.
     tattoy: super::tattoyer::Tattoyer,
+    /// The user's config for the plugin.
+    config: Config,
     /// The user's terminal colours.
     palette: crate::palette::converter::Palette,
-    /// The plugin's subprocess
-    child: std::process::Child,
-    /// STDIN to the plugin process, for sending messages to the plugin.
-    plugin_stdin: std::io::BufWriter<std::process::ChildStdin>,
-    /// Output stream from spawned plugin process.
-    parsed_messages_rx: tokio::sync::mpsc::Receiver<tattoy_protocol::PluginOutputMessages>,
+    /// The plugin's currently running process.
+    process: PluginProcess,
+    /// Whether the plugin process is currently running.
+    is_running: bool,
+    /// When the plugin should next be restarted, if it has crashed.
+    restart_at: Option<tokio::time::Instant>,
+    /// How many times the plugin has been restarted since it last ran stably.
+    restart_count: u32,
+    /// Descriptions of the most recent times that the plugin exited by itself.
+    restart_history: Vec<String>,
+    /// The kinds of user input that the plugin wants to receive.
+    input_kinds: Vec<tattoy_protocol::InputKind>,
+    /// The maximum rate at which the plugin wants to receive PTY updates.
+    frame_rate: Option<u32>,
+    /// The time at which the last PTY update was sent to the plugin.
+    last_pty_update: tokio::time::Instant,
+    /// Is there a PTY update that was held back because of the plugin's frame rate?
+    is_pty_update_pending: bool,
+    /// Whether the plugin wants to receive only the parts of the PTY screen that have changed.
+    is_pty_diff_subscribed: bool,
+    /// The sequence number of the last PTY update or diff sent to the plugin.
+    pty_sequence: u64,
+    /// The sequence number of Tattoy's copy of the PTY screen when it was last sent to the plugin.
+    /// `None` means that the plugin needs to be sent the entire screen.
+    sent_screen_seqno: Option<termwiz::surface::SequenceNo>,
+    /// The time at which the entire PTY screen was last sent to the plugin.
+    last_full_pty_update: tokio::time::Instant,
+    /// The shell's current working directory, if it has reported it with OSC 7.
+    working_directory: Option<String>,
+    /// Whether the plugin wants to receive the scrollback.
+    is_scrollback_subscribed: bool,
+    /// The number of scrollback lines that were last sent to the plugin.
+    sent_scrollback_height: usize,
+    /// The text of the scrollback lines that were on the screen when the scrollback was last sent
+    /// to the plugin. Lines above the screen can't change, so these are the only sent lines that
+    /// might need sending again. `None` means that the plugin needs to be sent the entire
+    /// scrollback.
+    sent_scrollback_tail: Option<Vec<String>>,
+    /// The scroll position that was last sent to the plugin.
+    sent_scroll_position: usize,
+    /// When each of the plugin's most recent notifications were shown.
+    notification_times: std::collections::VecDeque<tokio::time::Instant>,
+    /// Whether the user has already been told that the plugin's notifications are being dropped.
+    is_notification_limit_reported: bool,
+    /// Whether the user has already been told that the plugin tried to send input to the shell
+    /// without being allowed to.
+    is_input_injection_refusal_reported: bool,
+    /// Whether the user has already been told that the plugin is unresponsive.
+    is_unresponsive_reported: bool,
+    /// When the plugin has to have sent its capabilities by. `None` once it has sent them, or once
+    /// the user has been told that it didn't.
+    capabilities_deadline: Option<tokio::time::Instant>,
 }
 
 impl Plugin {
     /// Instatiate
     async fn new(
         config: &Config,
-        listener_rx: tokio::sync::oneshot::Receiver<crate::run::Protocol>,
         output_channel: tokio::sync::mpsc::Sender<crate::run::FrameUpdate>,
         palette: crate::palette::converter::Palette,
         state: std::sync::Arc<crate::shared_state::SharedState>,
     ) -> Result<Self> {
-        let tattoy = super::tattoyer::Tattoyer::new(
+        let mut tattoy = super::tattoyer::Tattoyer::new(
             config.name.clone(),
             std::sync::Arc::clone(&state),
             config.layer.unwrap_or(DEFAULT_LAYER),
@@ -56,30 +273,39 @@ impl Plugin {
             output_channel,
         )
         .await;
-        let (parsed_messages_tx, parsed_messages_rx) = tokio::sync::mpsc::channel(16);
+        tattoy.blend_mode = config.blend_mode.unwrap_or_default();
+        let working_directory = state.get_working_directory().await;
 
-        tracing::debug!(
-            "Spawing plugin, '{}', with: {}",
-            config.name,
-            config.path.display()
-        );
-        let result = Self::spawn(config.clone(), listener_rx, parsed_messages_tx, state);
+        let result = Self::spawn(config.clone(), state);
         match result {
-            Ok(mut child) => {
-                let stdin = child
-                    .stdin
-                    .take()
-                    .context("Couldn't get STDIN for plugin.")?;
-                let stdin_writer = std::io::BufWriter::new(stdin);
-
-                Ok(Self {
-                    tattoy,
-                    palette,
-                    child,
-                    plugin_stdin: stdin_writer,
-                    parsed_messages_rx,
-                })
-            }
+            Ok(process) => Ok(Self {
+                tattoy,
+                config: config.clone(),
+                palette,
+                process,
+                is_running: true,
+                restart_at: None,
+                restart_count: 0,
+                restart_history: Vec::new(),
+                input_kinds: config.input.clone().unwrap_or_default(),
+                frame_rate: None,
+                last_pty_update: tokio::time::Instant::now(),
+                is_pty_update_pending: false,
+                is_pty_diff_subscribed: false,
+                pty_sequence: 0,
+                sent_screen_seqno: None,
+                last_full_pty_update: tokio::time::Instant::now(),
+                working_directory,
+                is_scrollback_subscribed: false,
+                sent_scrollback_height: 0,
+                sent_scrollback_tail: None,
+                sent_scroll_position: 0,
+                notification_times: std::collections::VecDeque::new(),
+                is_notification_limit_reported: false,
+                is_input_injection_refusal_reported: false,
+                is_unresponsive_reported: false,
+                capabilities_deadline: Some(tokio::time::Instant::now() + HANDSHAKE_TIMEOUT),
+            }),
             Err(error) => {
                 tracing::error!("Couldn't start plugin {}: {error:?}", config.name);
                 Err(error)
@@ -96,17 +322,10 @@ impl Plugin {
     ) -> Result<()> {
         tracing::info!("Starting plugin: {}", config.name);
 
-        let (listener_tx, listener_rx) = tokio::sync::oneshot::channel();
         let mut tattoy_protocol_receiver = state.protocol_tx.subscribe();
 
-        let plugin_result = Self::new(
-            &config,
-            listener_rx,
-            output,
-            palette,
-            std::sync::Arc::clone(&state),
-        )
-        .await;
+        let plugin_result =
+            Self::new(&config, output, palette, std::sync::Arc::clone(&state)).await;
         let mut plugin = match plugin_result {
             Ok(plugin) => plugin,
             Err(error) => {
@@ -122,31 +341,66 @@ impl Plugin {
                 color_eyre::eyre::bail!(message);
             }
         };
+        plugin.send_hello()?;
+        plugin.send_palette()?;
+
+        let mut watchdog = tokio::time::interval(WATCHDOG_INTERVAL);
+        watchdog.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
 
         #[expect(
             clippy::integer_division_remainder_used,
             reason = "This is caused by the `tokio::select!`"
         )]
         loop {
+            // These can't be called inside `tokio::select!` because the plugin is already mutably
+            // borrowed there.
+            let pty_update_wait = plugin.pty_update_wait();
+            let restart_wait = plugin.restart_wait();
+            let handshake_wait = plugin.handshake_wait();
+
             tokio::select! {
-                Some(message) = plugin.parsed_messages_rx.recv() => {
-                    let result = plugin.render(message).await;
+                maybe_message = plugin.process.parsed_messages_rx.recv(), if plugin.is_running => {
+                    if let Some(message) = maybe_message {
+                        let result = plugin.handle_plugin_message(message).await;
+                        if let Err(error) = result {
+                            tracing::error!("{error:?}");
+                        }
+                    } else {
+                        // The listener only ever stops sending by itself when the plugin's
+                        // output has gone away.
+                        plugin.handle_exit().await?;
+                    }
+                },
+                () = tokio::time::sleep(pty_update_wait), if plugin.is_running && plugin.is_pty_update_pending => {
+                    let result = plugin.send_pty_output();
                     if let Err(error) = result {
-                        tracing::error!("{error:?}");
+                        tracing::error!("Couldn't send PTY update to plugin: {error:?}");
                     }
                 },
+                () = tokio::time::sleep(handshake_wait), if plugin.is_running && plugin.capabilities_deadline.is_some() => {
+                    plugin.capabilities_deadline = None;
+                    plugin
+                        .report_unknown_protocol_version("It didn't send its capabilities.")
+                        .await;
+                },
+                () = tokio::time::sleep(restart_wait), if plugin.restart_at.is_some() => {
+                    plugin.restart().await;
+                },
+                _ = watchdog.tick(), if plugin.is_running => {
+                    plugin.check_responsiveness().await?;
+                },
                 Ok(message) = tattoy_protocol_receiver.recv() => {
                     if matches!(message, crate::run::Protocol::End) {
-                        plugin.child.kill()?;
-                        let result = listener_tx.send(message);
-                        if let Err(error) = result {
-                            tracing::error!("Couldn't send End message to listener: {error:?}");
-                        }
-                        tracing::info!("Sent kill to plugin process and our plugin listener.");
+                        plugin.stop(message).await?;
                         break;
                     }
-                    plugin.handle_protocol_messages(&message)?;
-                    plugin.tattoy.handle_common_protocol_messages(message)?;
+                    if let crate::run::Protocol::Config(config) = &message {
+                        if !plugin.reload_config(&config.plugins).await? {
+                            plugin.disable().await?;
+                            break;
+                        }
+                    }
+                    plugin.handle_protocol_messages(message).await?;
                 }
             }
         }
@@ -156,275 +410,1976 @@ impl Plugin {
         Ok(())
     }
 
-    /// Handle Tattoy protocol messages.
-    fn handle_protocol_messages(&mut self, message: &crate::run::Protocol) -> Result<()> {
-        #[expect(
-            clippy::wildcard_enum_match_arm,
-            reason = "We're just handling the common cases here."
-        )]
-        match message {
-            crate::run::Protocol::Resize { width, height } => {
-                self.send_tty_size(*width, *height)?;
-            }
-            crate::run::Protocol::Output(_) => self.send_pty_output()?,
+    /// Stop a plugin that has been disabled, or removed from the config, and clear its last frame.
+    async fn disable(&mut self) -> Result<()> {
+        tracing::info!("Stopping disabled plugin: {}", self.config.name);
+        self.stop(crate::run::Protocol::End).await?;
+        self.tattoy.send_blank_output().await
+    }
 
-            _ => (),
+    /// Kill the plugin process and its output listener. The plugin's keybindings are released, so
+    /// that those keys go to the user's shell again.
+    async fn stop(&mut self, message: crate::run::Protocol) -> Result<()> {
+        self.release_keybindings().await;
+        if !self.is_running {
+            return Ok(());
+        }
+
+        // The listener has to be told first, otherwise it thinks that the plugin exited by itself.
+        if let Some(listener_tx) = self.process.listener_tx.take() {
+            let result = listener_tx.send(message);
+            if let Err(error) = result {
+                tracing::error!("Couldn't send End message to listener: {error:?}");
+            }
         }
+        self.kill()?;
+        tracing::info!("Sent kill to plugin process and our plugin listener.");
 
         Ok(())
     }
 
-    /// Send the new terminal size to the plugin.
-    fn send_tty_size(&mut self, width: u16, height: u16) -> Result<()> {
-        let json = serde_json::to_string(&tattoy_protocol::PluginInputMessages::TTYResize {
-            width,
-            height,
-        })?;
+    /// Give up all the key chords that the plugin has claimed.
+    async fn release_keybindings(&self) {
+        self.tattoy
+            .state
+            .plugin_keybindings
+            .write()
+            .await
+            .remove(&self.tattoy.id);
+    }
 
-        tracing::trace!("Sending JSON to plugin: {json}");
-        self.plugin_stdin.write_all(json.as_bytes())?;
-        self.plugin_stdin.write_all(b"\n")?;
-        self.plugin_stdin.flush()?;
+    /// Kill the plugin process, or disconnect from its socket. The listener then notices that the
+    /// plugin has gone.
+    fn kill(&mut self) -> Result<()> {
+        self.process.outbox.close();
+        match &mut self.process.connection {
+            Connection::Process(child) => child.kill()?,
+            #[cfg(not(target_os = "windows"))]
+            Connection::Socket(stream) => stream.shutdown(std::net::Shutdown::Both)?,
+            Connection::Wasm(instance) => instance.kill(),
+        }
 
         Ok(())
     }
 
-    /// Send Tattoy's PTY output to the plugin.
-    fn send_pty_output(&mut self) -> Result<()> {
-        let mut cells = Vec::<tattoy_protocol::Cell>::new();
-        for (y, line) in self.tattoy.screen.surface.screen_cells().iter().enumerate() {
-            for (x, cell) in line.iter().enumerate() {
-                let character = cell.str();
-                if character.is_empty() || character == " " {
-                    continue;
-                }
+    /// Check whether the plugin has stopped reading its input. If it has, the user is told, and if
+    /// they've configured it, the plugin is killed, which means that it is then restarted like any
+    /// other crashed plugin.
+    async fn check_responsiveness(&mut self) -> Result<()> {
+        let timeout = std::time::Duration::from_secs(
+            self.config
+                .response_timeout
+                .unwrap_or(DEFAULT_RESPONSE_TIMEOUT),
+        );
 
-                // TODO: how to avoid the clone?
-                self.palette
-                    .cell_attributes_to_true_colour(cell.clone().attrs_mut());
+        // Plugins don't have to send anything back, so the only sign that one has hung is that it
+        // has stopped reading the messages that we send it.
+        let problem = match self.process.outbox.stalled_for() {
+            Some(stalled_for) if stalled_for >= timeout => format!(
+                "It hasn't read any messages from Tattoy for {}s.",
+                stalled_for.as_secs()
+            ),
+            _ => {
+                self.is_unresponsive_reported = false;
+                return Ok(());
+            }
+        };
 
-                let bg_attribute =
-                    crate::blender::Blender::extract_colour(cell.attrs().background());
-                let bg = match bg_attribute {
-                    Some(attribute) => attribute.to_tuple_rgba(),
-                    None => self.palette.background_colour().into(),
-                };
+        let is_killed = self.config.kill_when_unresponsive.unwrap_or(false);
+        if self.is_unresponsive_reported && !is_killed {
+            return Ok(());
+        }
+        self.is_unresponsive_reported = true;
 
-                let fg_attribute =
-                    crate::blender::Blender::extract_colour(cell.attrs().foreground());
-                let fg = match fg_attribute {
-                    Some(attribute) => attribute.to_tuple_rgba(),
-                    None => self.palette.foreground_colour().into(),
-                };
+        tracing::warn!("Plugin '{}' is unresponsive: {problem}", self.tattoy.id);
+        let body = if is_killed {
+            format!("{problem} It has been stopped.")
+        } else {
+            problem
+        };
+        self.tattoy
+            .state
+            .send_notification(
+                format!("'{}' plugin is unresponsive", self.tattoy.id).as_str(),
+                crate::tattoys::notifications::message::Level::Warn,
+                Some(body),
+                false,
+            )
+            .await;
 
-                cells.push(
-                    tattoy_protocol::Cell::builder()
-                        .character(character.to_owned().chars().nth(0).context(
-                            "Couldn't get first character from cell, should be impossible.",
-                        )?)
-                        .coordinates((u32::try_from(x)?, u32::try_from(y)?))
-                        .maybe_bg(Some(bg))
-                        .maybe_fg(Some(fg))
-                        .build(),
-                );
-            }
+        if is_killed {
+            self.kill()?;
         }
 
-        let cursor_position = self.tattoy.screen.surface.cursor_position();
-        let json = serde_json::to_string(&tattoy_protocol::PluginInputMessages::PTYUpdate {
-            size: (self.tattoy.width, self.tattoy.height),
-            cells,
-            cursor: (cursor_position.0.try_into()?, cursor_position.1.try_into()?),
-        })?;
-        tracing::trace!("Sending JSON to plugin: {json}");
-        self.plugin_stdin.write_all(json.as_bytes())?;
-        self.plugin_stdin.write_all(b"\n")?;
-        self.plugin_stdin.flush()?;
-
         Ok(())
     }
 
-    /// Spawn the plugin process.
-    fn spawn(
-        config: Config,
-        mut listener_rx: tokio::sync::oneshot::Receiver<crate::run::Protocol>,
-        parsed_messages_tx: tokio::sync::mpsc::Sender<tattoy_protocol::PluginOutputMessages>,
-        state: std::sync::Arc<crate::shared_state::SharedState>,
-    ) -> Result<std::process::Child> {
-        let mut cmd = std::process::Command::new(
-            config
-                .path
-                .to_str()
-                .context("Couldn't convert plugin path to string")?,
+    /// Called when the plugin process has exited by itself, most likely because it crashed.
+    async fn handle_exit(&mut self) -> Result<()> {
+        self.is_running = false;
+        let uptime = self.process.started_at.elapsed();
+        let exit_status = self.reap();
+        tracing::warn!(
+            "Plugin '{}' exited with {exit_status} after running for {uptime:?}",
+            self.tattoy.id
         );
-        cmd.stdout(std::process::Stdio::piped());
-        cmd.stderr(std::process::Stdio::piped());
-        cmd.stdin(std::process::Stdio::piped());
-
-        let mut child = cmd.spawn()?;
 
-        let stdout = child
-            .stdout
-            .take()
-            .context("Couldn't take STDOUT from plugin.")?;
-        // TODO:
-        //   By not taking advantage of async this may turn out to be a bad idea.
-        //   See this issue for progress on supporting async stream deserialisation:
-        //     https://github.com/serde-rs/json/issues/316
-        let mut stdout_reader = std::io::BufReader::new(stdout);
+        self.restart_history.push(format!(
+            "Exited with {exit_status} after {}s",
+            uptime.as_secs()
+        ));
+        if uptime >= STABLE_RUN_DURATION {
+            self.restart_count = 0;
+        }
 
-        let mut stderr = child
-            .stderr
-            .take()
-            .context("Couldn't take STDERR from plugin.")?;
+        self.release_keybindings().await;
+        self.input_kinds = self.config.input.clone().unwrap_or_default();
+        self.frame_rate = None;
+        self.is_pty_update_pending = false;
+        self.is_pty_diff_subscribed = false;
+        self.sent_screen_seqno = None;
+        self.is_scrollback_subscribed = false;
+        self.sent_scrollback_tail = None;
+        self.is_unresponsive_reported = false;
+        self.capabilities_deadline = None;
 
-        let tokio_runtime = tokio::runtime::Handle::current();
-        std::thread::spawn(move || {
-            tokio_runtime.block_on(async {
-                tracing::trace!("Starting to parse JSON stream from plugin...");
-                let mut did_plugin_exit_by_itself = false;
-                loop {
-                    tracing::debug!("(Re)starting parser");
-                    let result = Self::listener(&mut stdout_reader, &parsed_messages_tx).await;
-                    if result.is_err() {
-                        did_plugin_exit_by_itself = true;
-                        break;
-                    }
-                    match listener_rx.try_recv() {
-                        Ok(message) => {
-                            if matches!(message, crate::run::Protocol::End) {
-                                break;
-                            }
-                        }
-                        Err(error) => match error {
-                            tokio::sync::oneshot::error::TryRecvError::Empty => (),
-                            tokio::sync::oneshot::error::TryRecvError::Closed => {
-                                did_plugin_exit_by_itself = true;
-                                break;
-                            }
-                        },
-                    }
-                    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
-                }
-                tracing::debug!("Leaving plugin listener loop.");
+        // Don't leave the plugin's last frame lingering in the compositor.
+        self.tattoy.send_blank_output().await?;
 
-                if did_plugin_exit_by_itself {
-                    let mut error_output = String::new();
-                    stderr
-                        .read_to_string(&mut error_output)
-                        .unwrap_or_else(|error| {
-                            tracing::error!("Couldn't read STDERR from plugin process: {error:?}");
-                            0
-                        });
-                    error_output = format!("STDERR output:\n{error_output}");
-                    state
-                        .send_notification(
-                            format!("'{}' plugin exited", config.name).as_str(),
-                            crate::tattoys::notifications::message::Level::Error,
-                            Some(error_output),
-                            false,
-                        )
-                        .await;
-                }
-            });
-        });
+        self.schedule_restart().await;
 
-        Ok(child)
+        Ok(())
     }
 
-    /// Parse output from the plugin, byte by byte, sending a message whenever it finds a valid
-    /// JSON plugin protocol message.
-    ///
-    /// Apart from JSON not being the most efficient IPC medium, it also may not be the most
-    /// efficient to use this streaming parser, as it requires checking for a valid message on
-    /// every new byte. The benefit however is that plugin authors do not need to worry about the
-    /// format of their messages. Therefore, there's no need to use delimeters of any kind.
-    async fn listener(
-        reader: &mut std::io::BufReader<std::process::ChildStdout>,
-        parsed_messages_tx: &tokio::sync::mpsc::Sender<tattoy_protocol::PluginOutputMessages>,
-    ) -> Result<()> {
-        let mut messages = serde_json::Deserializer::from_reader(reader)
-            .into_iter::<tattoy_protocol::PluginOutputMessages>();
-
-        // This is how we detect whether the plugin process has exited. I don't actually know how
-        // reliable this is. Ideally we'd listen to `child.wait()` in a separate thread.
-        if messages.by_ref().peekable().peek().is_none() {
-            let message = "STDIN has gone away";
-            tracing::warn!(message);
-            color_eyre::eyre::bail!(message);
-        }
+    /// Make sure the plugin process has fully exited and describe how it exited.
+    fn reap(&mut self) -> String {
+        let child = match &mut self.process.connection {
+            Connection::Process(child) => child,
+            #[cfg(not(target_os = "windows"))]
+            Connection::Socket(_) => return "closed socket".to_owned(),
+            Connection::Wasm(instance) => return instance.reap(),
+        };
 
-        for parse_result in messages.by_ref() {
-            match parse_result {
-                Ok(message) => {
-                    tracing::trace!("Parsed JSON message: {message:?}");
-                    let send_result = parsed_messages_tx.send(message).await;
-                    tracing::trace!("Sent JSON message");
-                    if let Err(error) = send_result {
-                        tracing::error!("Couldn't send parsed plugin message: {error:?}");
-                    }
+        match child.try_wait() {
+            Ok(Some(status)) => status.to_string(),
+            Ok(None) => {
+                // The plugin closed its STDOUT, or sent something that couldn't be read, but is
+                // still running, so it's of no use to us.
+                if let Err(error) = child.kill() {
+                    tracing::error!("Couldn't kill unresponsive plugin: {error:?}");
                 }
-                Err(error) => tracing::error!("Error parsing plugin message: {error:?}"),
+                if let Err(error) = child.wait() {
+                    tracing::error!("Couldn't wait for unresponsive plugin: {error:?}");
+                }
+                "closed STDOUT".to_owned()
             }
+            Err(error) => format!("unknown status ({error})"),
         }
+    }
 
-        Ok(())
+    /// Schedule the next restart of the plugin, or give up if it has already been restarted too
+    /// many times.
+    async fn schedule_restart(&mut self) {
+        let excess = self
+            .restart_history
+            .len()
+            .saturating_sub(RESTART_HISTORY_LENGTH);
+        self.restart_history.drain(..excess);
+        let history = self
+            .restart_history
+            .iter()
+            .map(|exit| format!("• {exit}"))
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let max_restarts = self.config.max_restarts.unwrap_or(DEFAULT_MAX_RESTARTS);
+        if self.restart_count >= max_restarts {
+            tracing::error!(
+                "Plugin '{}' won't be restarted, it has already been restarted {} times",
+                self.tattoy.id,
+                self.restart_count
+            );
+            self.tattoy
+                .state
+                .send_notification(
+                    format!("'{}' plugin stopped", self.tattoy.id).as_str(),
+                    crate::tattoys::notifications::message::Level::Error,
+                    Some(format!(
+                        "Gave up after {} restarts.\n{history}",
+                        self.restart_count
+                    )),
+                    false,
+                )
+                .await;
+            return;
+        }
+
+        self.restart_count = self.restart_count.saturating_add(1);
+        let backoff = Self::restart_backoff(self.restart_count);
+        self.restart_at = Some(tokio::time::Instant::now() + backoff);
+        tracing::info!(
+            "Restarting plugin '{}' in {backoff:?}, attempt {}",
+            self.tattoy.id,
+            self.restart_count
+        );
+
+        self.tattoy
+            .state
+            .send_notification(
+                format!("'{}' plugin restarting", self.tattoy.id).as_str(),
+                crate::tattoys::notifications::message::Level::Warn,
+                Some(format!(
+                    "Restart {} of {max_restarts} in {:.1}s.\n{history}",
+                    self.restart_count,
+                    backoff.as_secs_f32()
+                )),
+                false,
+            )
+            .await;
     }
 
-    /// Tick the render
-    async fn render(&mut self, output: tattoy_protocol::PluginOutputMessages) -> Result<()> {
-        self.tattoy.initialise_surface();
+    /// How long to wait before the given restart attempt. It doubles with every attempt.
+    fn restart_backoff(attempt: u32) -> std::time::Duration {
+        let multiplier = 2u32.saturating_pow(attempt.saturating_sub(1));
+        RESTART_BACKOFF_INITIAL
+            .saturating_mul(multiplier)
+            .min(RESTART_BACKOFF_MAX)
+    }
 
-        tracing::debug!("Rendering from plugin message");
-        match output {
-            tattoy_protocol::PluginOutputMessages::OutputText {
-                text,
-                coordinates,
-                bg,
-                fg,
-            } => {
-                self.tattoy.surface.add_text(
-                    coordinates.0.try_into()?,
-                    coordinates.1.try_into()?,
-                    text,
-                    bg,
-                    fg,
-                );
-            }
-            tattoy_protocol::PluginOutputMessages::OutputPixels(pixels) => {
-                for pixel in pixels {
-                    self.tattoy.surface.add_pixel(
-                        pixel.coordinates.0.try_into()?,
-                        pixel.coordinates.1.try_into()?,
-                        // TODO: use the terminal palette's default foreground colour
-                        pixel.color.unwrap_or(crate::surface::WHITE),
-                    )?;
-                }
-            }
-            tattoy_protocol::PluginOutputMessages::OutputCells(cells) => {
-                for cell in cells {
-                    self.tattoy.surface.add_text(
-                        cell.coordinates.0.try_into()?,
-                        cell.coordinates.1.try_into()?,
-                        cell.character.to_string(),
-                        cell.bg,
-                        cell.fg,
+    /// How long until the plugin is due to be restarted.
+    fn restart_wait(&self) -> std::time::Duration {
+        self.restart_at
+            .map_or(std::time::Duration::ZERO, |restart_at| {
+                restart_at.saturating_duration_since(tokio::time::Instant::now())
+            })
+    }
+
+    /// How long until the plugin has to have sent its capabilities.
+    fn handshake_wait(&self) -> std::time::Duration {
+        self.capabilities_deadline
+            .map_or(std::time::Duration::ZERO, |deadline| {
+                deadline.saturating_duration_since(tokio::time::Instant::now())
+            })
+    }
+
+    /// Start a fresh process for a plugin that had previously exited.
+    async fn restart(&mut self) {
+        self.restart_at = None;
+        tracing::info!("Restarting plugin: {}", self.tattoy.id);
+
+        let result = Self::spawn(
+            self.config.clone(),
+            std::sync::Arc::clone(&self.tattoy.state),
+        );
+        match result {
+            Ok(process) => {
+                self.process = process;
+                self.is_running = true;
+                self.capabilities_deadline = Some(tokio::time::Instant::now() + HANDSHAKE_TIMEOUT);
+
+                // If the plugin has already exited again, then this is picked up by the main loop.
+                let result = self.send_initial_state();
+                if let Err(error) = result {
+                    tracing::error!(
+                        "Couldn't send initial state to restarted plugin {}: {error:?}",
+                        self.tattoy.id
                     );
                 }
             }
+            Err(error) => {
+                tracing::error!("Couldn't restart plugin {}: {error:?}", self.tattoy.id);
+                self.restart_history
+                    .push(format!("Couldn't be started: {}", error.root_cause()));
+                self.schedule_restart().await;
+            }
+        }
+    }
 
-            #[expect(
-                clippy::unreachable,
-                reason = "
-                    The plugin protocol specifies `non-exhaustive`, but we are also the protocol definers,
-                    so we won't get hit by unexpeted protocol changes.
-                "
-            )]
-            _ => unreachable!(),
+    /// Apply the plugin's reloaded config. The layer, opacity and blend mode, and the options that
+    /// Tattoy checks whilst the plugin is running, like `response_timeout`, apply immediately.
+    /// Everything else, like the plugin's `path`, `args`, `encoding` or `settings`, only takes
+    /// effect when the plugin is next restarted. A plugin that Tattoy gave up restarting is started
+    /// again when the config for how it's started changes. Returns `false` if the plugin has been
+    /// disabled or removed from the config, in which case it should be stopped. Newly enabled
+    /// plugins are started by the loader.
+    async fn reload_config(&mut self, plugins: &[Config]) -> Result<bool> {
+        let Some(config) = plugins
+            .iter()
+            .find(|config| config.name == self.config.name)
+        else {
+            return Ok(false);
+        };
+        if !config.is_enabled() {
+            return Ok(false);
+        }
+
+        let layer = config.layer.unwrap_or(DEFAULT_LAYER);
+        let opacity = config.opacity.unwrap_or(DEFAULT_OPACITY);
+        let blend_mode = config.blend_mode.unwrap_or_default();
+        let is_given_up_on = !self.is_running && self.restart_at.is_none();
+        let is_started_differently = !self.config.is_started_the_same(config);
+        self.config = config.clone();
+        if is_given_up_on && is_started_differently {
+            tracing::info!(
+                "Plugin '{}' was changed after it stopped, so it's being started again",
+                self.config.name
+            );
+            self.restart_count = 0;
+            self.restart_history.clear();
+            self.restart().await;
+        }
+
+        let is_unchanged = layer == self.tattoy.layer
+            && (opacity - self.tattoy.opacity).abs() < f32::EPSILON
+            && blend_mode == self.tattoy.blend_mode;
+        if is_unchanged {
+            return Ok(true);
         }
 
+        tracing::debug!(
+            "Moving plugin '{}' to layer {layer} with opacity {opacity} and blend mode \
+            {blend_mode:?}",
+            self.config.name
+        );
+        self.tattoy.layer = layer;
+        self.tattoy.opacity = opacity;
+        self.tattoy.blend_mode = blend_mode;
+        // Re-send the plugin's current frame so that the change is visible straight away.
+        self.tattoy.surface.layer = layer;
+        self.tattoy.surface.opacity = opacity;
+        self.tattoy.surface.blend_mode = blend_mode;
         self.tattoy.send_output().await?;
 
-        Ok(())
+        Ok(true)
+    }
+
+    /// Bring a freshly started plugin up to date with the current state of the terminal.
+    fn send_initial_state(&mut self) -> Result<()> {
+        self.send_hello()?;
+        self.send_palette()?;
+        self.send_tty_size(self.tattoy.width, self.tattoy.height)?;
+        self.send_pty_output()
+    }
+
+    /// Handle Tattoy protocol messages.
+    async fn handle_protocol_messages(&mut self, message: crate::run::Protocol) -> Result<()> {
+        if matches!(message, crate::run::Protocol::Config(_)) {
+            self.reload_palette().await;
+        }
+
+        if self.is_running {
+            let result = self.forward_protocol_message(&message).await;
+            if let Err(error) = result {
+                tracing::error!("Couldn't send message to plugin: {error:?}");
+            }
+        }
+
+        if Self::is_screen_replaced(&message) {
+            self.sent_screen_seqno = None;
+        }
+        if Self::is_scrollback_replaced(&message) {
+            self.sent_scrollback_tail = None;
+        }
+        let is_output = matches!(message, crate::run::Protocol::Output(_));
+        let is_scrollback_output = Self::is_scrollback_output(&message);
+
+        // PTY output is sent to the plugin after our own copy of the PTY has been updated.
+        self.tattoy.handle_common_protocol_messages(message)?;
+
+        if self.is_running && is_output {
+            self.working_directory = self.tattoy.state.get_working_directory().await;
+            let result = if is_scrollback_output {
+                self.send_scrollback_update().await
+            } else {
+                self.send_or_defer_pty_output()
+            };
+            if let Err(error) = result {
+                tracing::error!("Couldn't send PTY output to plugin: {error:?}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Forward the relevant Tattoy protocol messages to the plugin.
+    async fn forward_protocol_message(&mut self, message: &crate::run::Protocol) -> Result<()> {
+        #[expect(
+            clippy::wildcard_enum_match_arm,
+            reason = "We're just handling the common cases here."
+        )]
+        match message {
+            crate::run::Protocol::Resize { width, height } => {
+                self.send_tty_size(*width, *height)?;
+            }
+            crate::run::Protocol::Input(input) => self.send_user_input(input).await?,
+
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// The encoding that the running plugin uses.
+    const fn encoding(&self) -> tattoy_protocol::encoding::Encoding {
+        self.process.encoding
+    }
+
+    /// Send a message to the plugin over its STDIN or socket.
+    fn send_message(&mut self, message: &tattoy_protocol::PluginInputMessages) -> Result<()> {
+        self.send_encoded_message(message, self.encoding())
+    }
+
+    /// Send a message to the plugin over its STDIN or socket, using the given encoding.
+    fn send_encoded_message(
+        &mut self,
+        message: &tattoy_protocol::PluginInputMessages,
+        encoding: tattoy_protocol::encoding::Encoding,
+    ) -> Result<()> {
+        let bytes = tattoy_protocol::encoding::encode(message, encoding)?;
+        let is_pty_update = matches!(
+            message,
+            tattoy_protocol::PluginInputMessages::PTYUpdate { .. }
+                | tattoy_protocol::PluginInputMessages::PTYDiff { .. }
+        );
+
+        tracing::trace!("Sending {encoding:?} message to plugin: {message:?}");
+        self.process.outbox.push(bytes, is_pty_update)
+    }
+
+    /// Start the handshake with the plugin. The plugin should reply with its capabilities.
+    ///
+    /// This is always sent as JSON because the plugin can't know what encoding to expect until
+    /// it has received it.
+    fn send_hello(&mut self) -> Result<()> {
+        let encoding = self.encoding();
+        self.send_encoded_message(
+            &tattoy_protocol::PluginInputMessages::Hello {
+                protocol_version: tattoy_protocol::PROTOCOL_VERSION,
+                tattoy_version: env!("CARGO_PKG_VERSION").to_owned(),
+                encoding,
+                settings: self.config.settings.clone().unwrap_or_default(),
+            },
+            tattoy_protocol::encoding::Encoding::Json,
+        )
+    }
+
+    /// Send the true colours of the user's terminal palette to the plugin.
+    fn send_palette(&mut self) -> Result<()> {
+        let colours = (0..=u8::MAX)
+            .map(|index| {
+                self.palette
+                    .true_colour_tuple_from_index(index)
+                    .to_tuple_rgba()
+            })
+            .collect();
+        self.send_message(&tattoy_protocol::PluginInputMessages::Palette {
+            colours,
+            foreground: self.palette.foreground_colour().to_tuple_rgba(),
+            background: self.palette.background_colour().to_tuple_rgba(),
+        })
+    }
+
+    /// The config watcher also notices changes to the palette file, so reload the palette whenever
+    /// the config changes and send it to the plugin.
+    async fn reload_palette(&mut self) {
+        let result =
+            crate::config::main::Config::load_palette(std::sync::Arc::clone(&self.tattoy.state))
+                .await;
+        match result {
+            Ok(palette) => self.palette = palette,
+            Err(error) => {
+                tracing::error!(
+                    "Couldn't reload palette for '{}' plugin: {error:?}",
+                    self.tattoy.id
+                );
+                return;
+            }
+        }
+
+        if self.is_running {
+            if let Err(error) = self.send_palette() {
+                tracing::error!("Couldn't send palette to plugin: {error:?}");
+            }
+        }
+    }
+
+    /// Send the new terminal size to the plugin.
+    fn send_tty_size(&mut self, width: u16, height: u16) -> Result<()> {
+        self.send_message(&tattoy_protocol::PluginInputMessages::TTYResize { width, height })
+    }
+
+    /// Send the end user's input to the plugin, but only if the plugin has opted in to receiving
+    /// that kind of input. Key events claimed by a plugin are only ever sent to that plugin.
+    async fn send_user_input(&mut self, input: &crate::raw_input::ParsedInput) -> Result<()> {
+        let Some((kind, message)) = Self::convert_user_input(&input.event) else {
+            return Ok(());
+        };
+
+        if let termwiz::input::InputEvent::Key(key_event) = &input.event {
+            let maybe_owner = self
+                .tattoy
+                .state
+                .get_plugin_keybinding_owner(key_event)
+                .await;
+            if let Some(owner) = maybe_owner {
+                if owner == self.tattoy.id {
+                    return self.send_message(&message);
+                }
+                return Ok(());
+            }
+        }
+
+        if !self.input_kinds.contains(&kind) {
+            return Ok(());
+        }
+
+        self.send_message(&message)
+    }
+
+    /// Convert a parsed input event into its plugin protocol equivalent.
+    fn convert_user_input(
+        event: &termwiz::input::InputEvent,
+    ) -> Option<(
+        tattoy_protocol::InputKind,
+        tattoy_protocol::PluginInputMessages,
+    )> {
+        match event {
+            termwiz::input::InputEvent::Key(key_event) => {
+                let key = if let termwiz::input::KeyCode::Char(character) = key_event.key {
+                    character.to_string()
+                } else {
+                    format!("{:?}", key_event.key)
+                };
+
+                Some((
+                    tattoy_protocol::InputKind::Key,
+                    tattoy_protocol::PluginInputMessages::KeyInput {
+                        key,
+                        modifiers: Self::convert_modifiers(key_event.modifiers),
+                    },
+                ))
+            }
+            termwiz::input::InputEvent::Mouse(mouse_event) => Some((
+                tattoy_protocol::InputKind::Mouse,
+                tattoy_protocol::PluginInputMessages::MouseInput {
+                    // Termwiz's mouse coordinates are 1-indexed.
+                    coordinates: (
+                        mouse_event.x.saturating_sub(1),
+                        mouse_event.y.saturating_sub(1),
+                    ),
+                    buttons: Self::convert_mouse_buttons(&mouse_event.mouse_buttons),
+                    modifiers: Self::convert_modifiers(mouse_event.modifiers),
+                },
+            )),
+            termwiz::input::InputEvent::Paste(text) => Some((
+                tattoy_protocol::InputKind::Paste,
+                tattoy_protocol::PluginInputMessages::PasteInput { text: text.clone() },
+            )),
+            termwiz::input::InputEvent::PixelMouse(_)
+            | termwiz::input::InputEvent::Resized { .. }
+            | termwiz::input::InputEvent::Wake => None,
+        }
+    }
+
+    /// Convert Termwiz's modifier flags to the plugin protocol's modifiers.
+    fn convert_modifiers(modifiers: termwiz::input::Modifiers) -> tattoy_protocol::Modifiers {
+        tattoy_protocol::Modifiers::builder()
+            .shift(modifiers.contains(termwiz::input::Modifiers::SHIFT))
+            .alt(modifiers.contains(termwiz::input::Modifiers::ALT))
+            .ctrl(modifiers.contains(termwiz::input::Modifiers::CTRL))
+            .super_key(modifiers.contains(termwiz::input::Modifiers::SUPER))
+            .build()
+    }
+
+    /// Convert Termwiz's mouse button flags to the plugin protocol's mouse buttons.
+    fn convert_mouse_buttons(
+        buttons: &termwiz::input::MouseButtons,
+    ) -> Vec<tattoy_protocol::MouseButton> {
+        let mut converted = Vec::new();
+
+        if buttons.contains(termwiz::input::MouseButtons::LEFT) {
+            converted.push(tattoy_protocol::MouseButton::Left);
+        }
+        if buttons.contains(termwiz::input::MouseButtons::RIGHT) {
+            converted.push(tattoy_protocol::MouseButton::Right);
+        }
+        if buttons.contains(termwiz::input::MouseButtons::MIDDLE) {
+            converted.push(tattoy_protocol::MouseButton::Middle);
+        }
+
+        let is_positive = buttons.contains(termwiz::input::MouseButtons::WHEEL_POSITIVE);
+        if buttons.contains(termwiz::input::MouseButtons::VERT_WHEEL) {
+            converted.push(if is_positive {
+                tattoy_protocol::MouseButton::WheelUp
+            } else {
+                tattoy_protocol::MouseButton::WheelDown
+            });
+        }
+        if buttons.contains(termwiz::input::MouseButtons::HORZ_WHEEL) {
+            converted.push(if is_positive {
+                tattoy_protocol::MouseButton::WheelLeft
+            } else {
+                tattoy_protocol::MouseButton::WheelRight
+            });
+        }
+
+        converted
+    }
+
+    /// How long until the plugin is ready for its next PTY update, based on its preferred frame
+    /// rate.
+    fn pty_update_wait(&self) -> std::time::Duration {
+        let Some(frame_rate) = self.frame_rate else {
+            return std::time::Duration::ZERO;
+        };
+
+        let interval = std::time::Duration::from_micros(
+            crate::renderer::ONE_MICROSECOND.wrapping_div(frame_rate.max(1).into()),
+        );
+        interval.saturating_sub(self.last_pty_update.elapsed())
+    }
+
+    /// Send the PTY output to the plugin, unless that would exceed the plugin's preferred frame
+    /// rate, in which case the update is sent once the plugin is ready.
+    fn send_or_defer_pty_output(&mut self) -> Result<()> {
+        if self.pty_update_wait().is_zero() {
+            return self.send_pty_output();
+        }
+
+        self.is_pty_update_pending = true;
+        Ok(())
+    }
+
+    /// Send Tattoy's PTY output to the plugin. Plugins that have opted in to PTY diffs are only
+    /// sent the rows that have changed, apart from the periodic full resync.
+    fn send_pty_output(&mut self) -> Result<()> {
+        self.last_pty_update = tokio::time::Instant::now();
+        self.is_pty_update_pending = false;
+        self.pty_sequence = self.pty_sequence.wrapping_add(1);
+
+        // A diff can't replace an update that the plugin hasn't read yet, only a full update can.
+        if self.process.outbox.has_pending_pty_update() {
+            self.sent_screen_seqno = None;
+        }
+
+        let is_resync_due = self.last_full_pty_update.elapsed() >= PTY_RESYNC_INTERVAL;
+        let maybe_diff_since = self
+            .sent_screen_seqno
+            .filter(|_| self.is_pty_diff_subscribed && !is_resync_due);
+
+        let surface = &self.tattoy.screen.surface;
+        let mut cells = Vec::<tattoy_protocol::Cell>::new();
+        let mut changed_rows = Vec::<u16>::new();
+        for (y, line) in surface.screen_lines().iter().enumerate() {
+            if let Some(diff_since) = maybe_diff_since {
+                if line.current_seqno() < diff_since {
+                    continue;
+                }
+                changed_rows.push(y.try_into()?);
+            }
+
+            for cell in line.visible_cells() {
+                let maybe_protocol_cell = Self::make_protocol_cell(
+                    &self.palette,
+                    cell.str(),
+                    (cell.cell_index(), y),
+                    cell.attrs(),
+                )?;
+                if let Some(protocol_cell) = maybe_protocol_cell {
+                    cells.push(protocol_cell);
+                }
+            }
+        }
+
+        let cursor_position = surface.cursor_position();
+        let cursor = (cursor_position.0.try_into()?, cursor_position.1.try_into()?);
+        let cursor_shape = Self::convert_cursor_shape(surface.cursor_shape());
+        let is_cursor_visible =
+            surface.cursor_visibility() == termwiz::surface::CursorVisibility::Visible;
+        let title = Some(surface.title().to_owned()).filter(|title| !title.is_empty());
+        let screen_seqno = surface.current_seqno();
+
+        let message = if maybe_diff_since.is_some() {
+            tattoy_protocol::PluginInputMessages::PTYDiff {
+                sequence: self.pty_sequence,
+                size: (self.tattoy.width, self.tattoy.height),
+                changed_rows,
+                cells,
+                cursor,
+                cursor_shape,
+                is_cursor_visible,
+                is_alternate_screen: self.tattoy.is_alternate_screen(),
+                title,
+                cwd: self.working_directory.clone(),
+            }
+        } else {
+            self.last_full_pty_update = tokio::time::Instant::now();
+            tattoy_protocol::PluginInputMessages::PTYUpdate {
+                sequence: self.pty_sequence,
+                size: (self.tattoy.width, self.tattoy.height),
+                cells,
+                cursor,
+                cursor_shape,
+                is_cursor_visible,
+                is_alternate_screen: self.tattoy.is_alternate_screen(),
+                title,
+                cwd: self.working_directory.clone(),
+            }
+        };
+
+        // If the message can't be sent, then the next one has to include everything that this one
+        // would have.
+        self.send_message(&message)?;
+        self.sent_screen_seqno = Some(screen_seqno);
+        Ok(())
+    }
+
+    /// Convert Termwiz's cursor shape to the plugin protocol's cursor shape.
+    const fn convert_cursor_shape(
+        maybe_shape: Option<termwiz::surface::CursorShape>,
+    ) -> tattoy_protocol::CursorShape {
+        let Some(shape) = maybe_shape else {
+            return tattoy_protocol::CursorShape::Default;
+        };
+
+        match shape {
+            termwiz::surface::CursorShape::Default => tattoy_protocol::CursorShape::Default,
+            termwiz::surface::CursorShape::BlinkingBlock => {
+                tattoy_protocol::CursorShape::BlinkingBlock
+            }
+            termwiz::surface::CursorShape::SteadyBlock => tattoy_protocol::CursorShape::SteadyBlock,
+            termwiz::surface::CursorShape::BlinkingUnderline => {
+                tattoy_protocol::CursorShape::BlinkingUnderline
+            }
+            termwiz::surface::CursorShape::SteadyUnderline => {
+                tattoy_protocol::CursorShape::SteadyUnderline
+            }
+            termwiz::surface::CursorShape::BlinkingBar => tattoy_protocol::CursorShape::BlinkingBar,
+            termwiz::surface::CursorShape::SteadyBar => tattoy_protocol::CursorShape::SteadyBar,
+        }
+    }
+
+    /// Convert a Tattoy cell into a plugin protocol cell. Blank cells aren't converted.
+    fn make_protocol_cell(
+        palette: &crate::palette::converter::Palette,
+        character: &str,
+        coordinates: (usize, usize),
+        attributes: &termwiz::cell::CellAttributes,
+    ) -> Result<Option<tattoy_protocol::Cell>> {
+        if character.is_empty() || character == " " {
+            return Ok(None);
+        }
+
+        let mut attributes = attributes.clone();
+        palette.cell_attributes_to_true_colour(&mut attributes);
+
+        let bg_attribute = crate::blender::Blender::extract_colour(attributes.background());
+        let bg = match bg_attribute {
+            Some(attribute) => attribute.to_tuple_rgba(),
+            None => palette.background_colour().into(),
+        };
+
+        let fg_attribute = crate::blender::Blender::extract_colour(attributes.foreground());
+        let fg = match fg_attribute {
+            Some(attribute) => attribute.to_tuple_rgba(),
+            None => palette.foreground_colour().into(),
+        };
+
+        Ok(Some(
+            tattoy_protocol::Cell::builder()
+                .character(
+                    character
+                        .chars()
+                        .nth(0)
+                        .context("Couldn't get first character from cell, should be impossible.")?,
+                )
+                .coordinates((u32::try_from(coordinates.0)?, u32::try_from(coordinates.1)?))
+                .maybe_bg(Some(bg))
+                .maybe_fg(Some(fg))
+                .build(),
+        ))
+    }
+
+    /// Is the message output from the underlying terminal that changes the scrollback?
+    const fn is_scrollback_output(message: &crate::run::Protocol) -> bool {
+        matches!(
+            message,
+            crate::run::Protocol::Output(
+                shadow_terminal::output::native::Output::Diff(
+                    shadow_terminal::output::native::SurfaceDiff::Scrollback(_)
+                ) | shadow_terminal::output::native::Output::Complete(
+                    shadow_terminal::output::native::CompleteSurface::Scrollback(_)
+                )
+            )
+        )
+    }
+
+    /// Does the message mean that the plugin's copy of the PTY screen can no longer be updated
+    /// with just a diff?
+    const fn is_screen_replaced(message: &crate::run::Protocol) -> bool {
+        matches!(
+            message,
+            crate::run::Protocol::Resize { .. }
+                | crate::run::Protocol::Output(shadow_terminal::output::native::Output::Complete(
+                    shadow_terminal::output::native::CompleteSurface::Screen(_)
+                ))
+        )
+    }
+
+    /// Does the message mean that the plugin's copy of the scrollback can no longer be updated with
+    /// just the lines at its end?
+    const fn is_scrollback_replaced(message: &crate::run::Protocol) -> bool {
+        matches!(
+            message,
+            crate::run::Protocol::Resize { .. }
+                | crate::run::Protocol::Output(shadow_terminal::output::native::Output::Complete(
+                    shadow_terminal::output::native::CompleteSurface::Scrollback(_)
+                ))
+        )
+    }
+
+    /// Send the scrollback to the plugin, if it has opted in to receiving it. Only the lines that
+    /// have changed or been added since the last update are sent, unless the scrollback has been
+    /// replaced, in which case the entire scrollback is sent.
+    async fn send_scrollback_update(&mut self) -> Result<()> {
+        if !self.is_scrollback_subscribed {
+            return Ok(());
+        }
+
+        let scrollback = self.tattoy.state.shadow_tty_scrollback.read().await;
+        let screen_lines = scrollback.surface.screen_lines();
+        let position = scrollback.position;
+
+        let maybe_start = self.sent_scrollback_tail.as_ref().and_then(|sent_tail| {
+            Self::find_first_changed_line(sent_tail, self.sent_scrollback_height, &screen_lines)
+        });
+        let is_unchanged = maybe_start == Some(screen_lines.len());
+        if is_unchanged && position == self.sent_scroll_position {
+            return Ok(());
+        }
+        let start = maybe_start.unwrap_or(0);
+
+        let mut lines = Vec::<Vec<tattoy_protocol::Cell>>::new();
+        for (y, line) in screen_lines.iter().enumerate().skip(start) {
+            let mut cells = Vec::<tattoy_protocol::Cell>::new();
+            for cell in line.visible_cells() {
+                let maybe_protocol_cell = Self::make_protocol_cell(
+                    &self.palette,
+                    cell.str(),
+                    (cell.cell_index(), y),
+                    cell.attrs(),
+                )?;
+                if let Some(protocol_cell) = maybe_protocol_cell {
+                    cells.push(protocol_cell);
+                }
+            }
+            lines.push(cells);
+        }
+
+        let tail_start = screen_lines
+            .len()
+            .saturating_sub(usize::from(self.tattoy.height));
+        let tail = screen_lines
+            .iter()
+            .skip(tail_start)
+            .map(|line| line.as_str().into_owned())
+            .collect::<Vec<String>>();
+        let height = screen_lines.len();
+        drop(screen_lines);
+        drop(scrollback);
+
+        self.send_message(&tattoy_protocol::PluginInputMessages::ScrollbackUpdate {
+            start: start.try_into()?,
+            height: height.try_into()?,
+            lines,
+            position: position.try_into()?,
+        })?;
+        self.sent_scrollback_height = height;
+        self.sent_scrollback_tail = Some(tail);
+        self.sent_scroll_position = position;
+        Ok(())
+    }
+
+    /// Find the index of the first scrollback line that has changed or been added since the
+    /// scrollback was last sent. Only the lines that were on the screen can have changed, so they
+    /// are the only ones that are compared. Returns `None` if the scrollback has shrunk.
+    fn find_first_changed_line(
+        sent_tail: &[String],
+        sent_height: usize,
+        lines: &[std::borrow::Cow<'_, termwiz::surface::line::Line>],
+    ) -> Option<usize> {
+        let tail_start = sent_height.checked_sub(sent_tail.len())?;
+        let current_tail = lines.get(tail_start..sent_height)?;
+        let first_changed = sent_tail
+            .iter()
+            .zip(current_tail)
+            .position(|(sent, line)| sent.as_str() != line.as_str())
+            .map_or(sent_height, |offset| tail_start + offset);
+
+        Some(first_changed)
+    }
+
+    /// Start the plugin, either by spawning its executable or by connecting to its socket.
+    fn spawn(
+        config: Config,
+        state: std::sync::Arc<crate::shared_state::SharedState>,
+    ) -> Result<PluginProcess> {
+        let (listener_tx, mut listener_rx) = tokio::sync::oneshot::channel();
+        let (parsed_messages_tx, parsed_messages_rx) = tokio::sync::mpsc::channel(16);
+
+        let kind = config.kind.unwrap_or_default();
+        let (connection, mut reader, writer, maybe_stderr) =
+            match (config.path.as_ref(), config.socket.as_ref()) {
+                (Some(path), None) => match kind {
+                    Kind::Native => Self::spawn_process(&config, path)?,
+                    Kind::Wasm => Self::spawn_wasm(&config, path)?,
+                },
+                (None, Some(socket)) => {
+                    if kind == Kind::Wasm {
+                        color_eyre::eyre::bail!("WebAssembly plugins need a `path` to their module")
+                    }
+                    if config.args.is_some() || config.env.is_some() || config.cwd.is_some() {
+                        color_eyre::eyre::bail!(
+                            "Plugin daemons are started outside of Tattoy, so they can't be given \
+                            `args`, `env` or `cwd`"
+                        )
+                    }
+                    Self::connect_socket(&config.name, socket)?
+                }
+                (Some(_), Some(_)) => {
+                    color_eyre::eyre::bail!("Plugins can't have both a `path` and a `socket`")
+                }
+                (None, None) => color_eyre::eyre::bail!("Plugins need a `path` or a `socket`"),
+            };
+
+        let stderr_capture = maybe_stderr.map(|stderr| {
+            Self::capture_stderr(config.name.clone(), stderr, std::sync::Arc::clone(&state))
+        });
+        let outbox = super::plugin_outbox::Outbox::start(config.name.clone(), writer);
+        let encoding = config.encoding.unwrap_or_default();
+        let tokio_runtime = tokio::runtime::Handle::current();
+        std::thread::spawn(move || {
+            tokio_runtime.block_on(async {
+                tracing::trace!("Starting to parse {encoding:?} stream from plugin...");
+                let mut did_plugin_exit_by_itself = false;
+                loop {
+                    tracing::debug!("(Re)starting parser");
+                    let result = if encoding == tattoy_protocol::encoding::Encoding::MessagePack {
+                        Self::message_pack_listener(&mut reader, &parsed_messages_tx).await
+                    } else {
+                        Self::listener(&mut reader, &parsed_messages_tx).await
+                    };
+                    // Stopping the plugin is also what makes the listener fail, so check for
+                    // that before blaming the plugin.
+                    let is_stopped = match listener_rx.try_recv() {
+                        Ok(message) => matches!(message, crate::run::Protocol::End),
+                        Err(error) => match error {
+                            tokio::sync::oneshot::error::TryRecvError::Empty => false,
+                            tokio::sync::oneshot::error::TryRecvError::Closed => {
+                                did_plugin_exit_by_itself = true;
+                                break;
+                            }
+                        },
+                    };
+                    if is_stopped {
+                        break;
+                    }
+                    if result.is_err() {
+                        did_plugin_exit_by_itself = true;
+                        break;
+                    }
+                    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+                }
+                tracing::debug!("Leaving plugin listener loop.");
+
+                // Closing the channel is what tells the main loop that the plugin has gone. It has
+                // to happen before waiting for STDERR, because if the plugin is actually still
+                // running, say it sent a corrupt message, then it's the main loop that kills it,
+                // and only then does its STDERR close.
+                drop(parsed_messages_tx);
+
+                if did_plugin_exit_by_itself {
+                    let error_output = if let Some(capture) = stderr_capture {
+                        let lines = capture.join().unwrap_or_else(|_panic| {
+                            tracing::error!("Thread capturing the plugin's STDERR panicked");
+                            std::collections::VecDeque::new()
+                        });
+                        let output = Vec::from(lines).join("\n");
+                        format!("STDERR output:\n{output}")
+                    } else {
+                        "The plugin closed its socket.".to_owned()
+                    };
+                    state
+                        .send_notification(
+                            format!("'{}' plugin exited", config.name).as_str(),
+                            crate::tattoys::notifications::message::Level::Error,
+                            Some(error_output),
+                            false,
+                        )
+                        .await;
+                }
+            });
+        });
+
+        Ok(PluginProcess {
+            connection,
+            outbox,
+            parsed_messages_rx,
+            listener_tx: Some(listener_tx),
+            started_at: tokio::time::Instant::now(),
+            encoding,
+        })
+    }
+
+    /// Read the plugin's STDERR line by line, as it's written. Each line goes to Tattoy's own log,
+    /// and to the plugin log overlay. The thread returns the most recent lines once the plugin
+    /// closes its STDERR, so that they can be shown to the user if the plugin exited by itself.
+    fn capture_stderr(
+        name: String,
+        stderr: Box<dyn std::io::Read + Send>,
+        state: std::sync::Arc<crate::shared_state::SharedState>,
+    ) -> std::thread::JoinHandle<std::collections::VecDeque<String>> {
+        std::thread::spawn(move || {
+            let _span = tracing::info_span!("plugin", name = %name).entered();
+            let mut recent_lines = std::collections::VecDeque::new();
+
+            for result in std::io::BufReader::new(stderr).lines() {
+                let line = match result {
+                    Ok(line) => line,
+                    Err(error) => {
+                        tracing::error!("Couldn't read STDERR from plugin: {error:?}");
+                        break;
+                    }
+                };
+                tracing::info!("{line}");
+                super::plugin_log::add_line(
+                    state
+                        .plugin_logs
+                        .blocking_write()
+                        .entry(name.clone())
+                        .or_default(),
+                    line.clone(),
+                );
+                super::plugin_log::add_line(&mut recent_lines, line);
+            }
+
+            recent_lines
+        })
+    }
+
+    /// Spawn the plugin's executable as a subprocess that we talk to over its STDIN and STDOUT.
+    fn spawn_process(config: &Config, path: &std::path::Path) -> Result<PluginStreams> {
+        tracing::debug!(
+            "Spawing plugin, '{}', with: {}",
+            config.name,
+            path.display()
+        );
+        let mut cmd = std::process::Command::new(
+            path.to_str()
+                .context("Couldn't convert plugin path to string")?,
+        );
+        cmd.args(config.args.iter().flatten());
+        cmd.envs(config.env.iter().flatten());
+        if let Some(cwd) = config.cwd.as_ref() {
+            cmd.current_dir(cwd);
+        }
+        cmd.stdout(std::process::Stdio::piped());
+        cmd.stderr(std::process::Stdio::piped());
+        cmd.stdin(std::process::Stdio::piped());
+
+        let mut child = cmd.spawn()?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .context("Couldn't take STDOUT from plugin.")?;
+        // TODO:
+        //   By not taking advantage of async this may turn out to be a bad idea.
+        //   See this issue for progress on supporting async stream deserialisation:
+        //     https://github.com/serde-rs/json/issues/316
+        let reader: PluginReader = std::io::BufReader::new(Box::new(stdout));
+
+        let stderr = child
+            .stderr
+            .take()
+            .context("Couldn't take STDERR from plugin.")?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .context("Couldn't get STDIN for plugin.")?;
+        let writer: PluginWriter = std::io::BufWriter::new(Box::new(stdin));
+
+        Ok((
+            Connection::Process(child),
+            reader,
+            writer,
+            Some(Box::new(stderr)),
+        ))
+    }
+
+    /// Run the plugin's WebAssembly module in Tattoy's embedded sandbox. We talk to it over its
+    /// WASI STDIN and STDOUT, just like a native plugin.
+    fn spawn_wasm(config: &Config, path: &std::path::Path) -> Result<PluginStreams> {
+        if config.cwd.is_some() {
+            color_eyre::eyre::bail!(
+                "WebAssembly plugins don't have access to the filesystem, so they can't have a `cwd`"
+            )
+        }
+        let args = config.args.clone().unwrap_or_default();
+        let env: Vec<(String, String)> = config.env.clone().into_iter().flatten().collect();
+        let (instance, stdout, stdin, stderr) =
+            super::wasm_plugin::Instance::spawn(&config.name, path, &args, &env)?;
+        let reader: PluginReader = std::io::BufReader::new(stdout);
+        let writer: PluginWriter = std::io::BufWriter::new(stdin);
+
+        Ok((Connection::Wasm(instance), reader, writer, Some(stderr)))
+    }
+
+    /// Connect to an already running plugin daemon over its Unix socket. A single daemon can serve
+    /// many Tattoy sessions.
+    #[cfg(not(target_os = "windows"))]
+    fn connect_socket(name: &str, socket: &std::path::Path) -> Result<PluginStreams> {
+        tracing::debug!("Connecting to plugin, '{name}', at: {}", socket.display());
+        let stream = match std::os::unix::net::UnixStream::connect(socket) {
+            Ok(stream) => stream,
+            Err(error) => color_eyre::eyre::bail!(
+                "Couldn't connect to plugin socket at {}: {error}",
+                socket.display()
+            ),
+        };
+
+        let reader: PluginReader = std::io::BufReader::new(Box::new(stream.try_clone()?));
+        let writer: PluginWriter = std::io::BufWriter::new(Box::new(stream.try_clone()?));
+
+        Ok((Connection::Socket(stream), reader, writer, None))
+    }
+
+    /// Unix sockets aren't available on Windows.
+    #[cfg(target_os = "windows")]
+    fn connect_socket(_name: &str, _socket: &std::path::Path) -> Result<PluginStreams> {
+        color_eyre::eyre::bail!("Plugin sockets are only supported on Unix-like systems")
+    }
+
+    /// Parse output from the plugin, byte by byte, sending a message whenever it finds a valid
+    /// JSON plugin protocol message.
+    ///
+    /// Apart from JSON not being the most efficient IPC medium, it also may not be the most
+    /// efficient to use this streaming parser, as it requires checking for a valid message on
+    /// every new byte. The benefit however is that plugin authors do not need to worry about the
+    /// format of their messages. Therefore, there's no need to use delimeters of any kind.
+    async fn listener(
+        reader: &mut PluginReader,
+        parsed_messages_tx: &tokio::sync::mpsc::Sender<tattoy_protocol::PluginOutputMessages>,
+    ) -> Result<()> {
+        let messages = serde_json::Deserializer::from_reader(reader)
+            .into_iter::<tattoy_protocol::PluginOutputMessages>();
+
+        let mut has_read_anything = false;
+        for parse_result in messages {
+            has_read_anything = true;
+            match parse_result {
+                Ok(message) => {
+                    tracing::trace!("Parsed JSON message: {message:?}");
+                    let send_result = parsed_messages_tx.send(message).await;
+                    tracing::trace!("Sent JSON message");
+                    if let Err(error) = send_result {
+                        tracing::error!("Couldn't send parsed plugin message: {error:?}");
+                    }
+                }
+                Err(error) => tracing::error!("Error parsing plugin message: {error:?}"),
+            }
+        }
+
+        // This is how we detect whether the plugin process has exited. I don't actually know how
+        // reliable this is. Ideally we'd listen to `child.wait()` in a separate thread.
+        if !has_read_anything {
+            let message = "STDIN has gone away";
+            tracing::warn!(message);
+            color_eyre::eyre::bail!(message);
+        }
+
+        Ok(())
+    }
+
+    /// Parse length-prefixed `MessagePack` output from the plugin, sending a message for every
+    /// frame that it reads.
+    async fn message_pack_listener(
+        reader: &mut PluginReader,
+        parsed_messages_tx: &tokio::sync::mpsc::Sender<tattoy_protocol::PluginOutputMessages>,
+    ) -> Result<()> {
+        let mut has_read_anything = false;
+        while let Some(frame) = tattoy_protocol::encoding::read_frame(reader)? {
+            has_read_anything = true;
+            match tattoy_protocol::encoding::decode::<tattoy_protocol::PluginOutputMessages>(
+                &frame,
+                tattoy_protocol::encoding::Encoding::MessagePack,
+            ) {
+                Ok(message) => {
+                    tracing::trace!("Parsed MessagePack message: {message:?}");
+                    let send_result = parsed_messages_tx.send(message).await;
+                    if let Err(error) = send_result {
+                        tracing::error!("Couldn't send parsed plugin message: {error:?}");
+                    }
+                }
+                Err(error) => tracing::error!("Error parsing plugin message: {error:?}"),
+            }
+        }
+
+        if !has_read_anything {
+            let message = "STDIN has gone away";
+            tracing::warn!(message);
+            color_eyre::eyre::bail!(message);
+        }
+
+        Ok(())
+    }
+
+    /// Handle a message from the plugin.
+    async fn handle_plugin_message(
+        &mut self,
+        message: tattoy_protocol::PluginOutputMessages,
+    ) -> Result<()> {
+        if let tattoy_protocol::PluginOutputMessages::Capabilities(capabilities) = message {
+            return self.register_capabilities(capabilities).await;
+        }
+
+        if let tattoy_protocol::PluginOutputMessages::Notification { title, body, level } = message
+        {
+            self.send_plugin_notification(&title, body, level).await;
+            return Ok(());
+        }
+
+        if let tattoy_protocol::PluginOutputMessages::SendInput(input) = message {
+            return self.inject_input(input).await;
+        }
+
+        self.render(message).await
+    }
+
+    /// Show a notification from the plugin to the user, unless the plugin has recently sent too
+    /// many.
+    async fn send_plugin_notification(
+        &mut self,
+        title: &str,
+        body: Option<String>,
+        level: tattoy_protocol::NotificationLevel,
+    ) {
+        let now = tokio::time::Instant::now();
+        while self
+            .notification_times
+            .front()
+            .is_some_and(|sent_at| now.duration_since(*sent_at) > NOTIFICATION_LIMIT_WINDOW)
+        {
+            self.notification_times.pop_front();
+        }
+
+        if self.notification_times.len() >= NOTIFICATION_LIMIT {
+            tracing::debug!(
+                "Dropping notification from '{}' plugin: {title}",
+                self.tattoy.id
+            );
+            if !self.is_notification_limit_reported {
+                self.is_notification_limit_reported = true;
+                self.tattoy
+                    .state
+                    .send_notification(
+                        format!(
+                            "'{}' plugin is sending too many notifications",
+                            self.tattoy.id
+                        )
+                        .as_str(),
+                        crate::tattoys::notifications::message::Level::Warn,
+                        Some("Some of its notifications won't be shown.".to_owned()),
+                        false,
+                    )
+                    .await;
+            }
+            return;
+        }
+
+        self.is_notification_limit_reported = false;
+        self.notification_times.push_back(now);
+        self.tattoy
+            .state
+            .send_notification(
+                format!("{}: {title}", self.tattoy.id).as_str(),
+                Self::convert_notification_level(level),
+                body,
+                false,
+            )
+            .await;
+    }
+
+    /// Send input from the plugin to the user's shell, but only if the user has allowed it.
+    async fn inject_input(&mut self, input: tattoy_protocol::ShellInput) -> Result<()> {
+        if !self.config.allow_input_injection.unwrap_or(false) {
+            tracing::warn!(
+                "Ignoring input from '{}' plugin because input injection isn't allowed",
+                self.tattoy.id
+            );
+            if !self.is_input_injection_refusal_reported {
+                self.is_input_injection_refusal_reported = true;
+                self.tattoy
+                    .state
+                    .send_notification(
+                        format!("'{}' plugin tried to send input", self.tattoy.id).as_str(),
+                        crate::tattoys::notifications::message::Level::Warn,
+                        Some(
+                            "Set `allow_input_injection = true` in its config to allow it."
+                                .to_owned(),
+                        ),
+                        false,
+                    )
+                    .await;
+            }
+            return Ok(());
+        }
+
+        let bytes = match input {
+            tattoy_protocol::ShellInput::Text(text) => text.into_bytes(),
+            tattoy_protocol::ShellInput::Bytes(bytes) => bytes,
+            _ => color_eyre::eyre::bail!("Unsupported input from plugin: {input:?}"),
+        };
+
+        tracing::trace!(
+            "Plugin '{}' is sending input to the PTY: {}",
+            self.tattoy.id,
+            String::from_utf8_lossy(&bytes)
+        );
+        self.tattoy
+            .state
+            .protocol_tx
+            .send(crate::run::Protocol::PTYInput(bytes))?;
+
+        Ok(())
+    }
+
+    /// Convert the plugin protocol's notification level to Tattoy's own notification level.
+    const fn convert_notification_level(
+        level: tattoy_protocol::NotificationLevel,
+    ) -> crate::tattoys::notifications::message::Level {
+        #[expect(
+            clippy::wildcard_enum_match_arm,
+            reason = "The protocol's levels are `non_exhaustive`, so we need a fallback"
+        )]
+        match level {
+            tattoy_protocol::NotificationLevel::Error => {
+                crate::tattoys::notifications::message::Level::Error
+            }
+            tattoy_protocol::NotificationLevel::Warn => {
+                crate::tattoys::notifications::message::Level::Warn
+            }
+            tattoy_protocol::NotificationLevel::Debug => {
+                crate::tattoys::notifications::message::Level::Debug
+            }
+            tattoy_protocol::NotificationLevel::Trace => {
+                crate::tattoys::notifications::message::Level::Trace
+            }
+            _ => crate::tattoys::notifications::message::Level::Info,
+        }
+    }
+
+    /// Register what the plugin has told us it's capable of and what it wants.
+    async fn register_capabilities(
+        &mut self,
+        capabilities: tattoy_protocol::Capabilities,
+    ) -> Result<()> {
+        tracing::debug!(
+            "Plugin '{}' sent its capabilities: {capabilities:?}",
+            self.tattoy.id
+        );
+
+        self.capabilities_deadline = None;
+        match capabilities.protocol_version {
+            Some(version) => self.check_protocol_version(version).await,
+            None => {
+                self.report_unknown_protocol_version(
+                    "It didn't say which version of the plugin protocol it uses.",
+                )
+                .await;
+            }
+        }
+
+        if let Some(name) = &capabilities.name {
+            tracing::debug!("Plugin '{}' identifies itself as '{name}'", self.tattoy.id);
+        }
+
+        if self.config.input.is_none() {
+            self.input_kinds = capabilities.input;
+        }
+        self.frame_rate = capabilities.frame_rate;
+        if capabilities.pty_diffs != self.is_pty_diff_subscribed {
+            self.is_pty_diff_subscribed = capabilities.pty_diffs;
+            self.sent_screen_seqno = None;
+        }
+        if capabilities.scrollback && !self.is_scrollback_subscribed {
+            self.is_scrollback_subscribed = true;
+            self.sent_scrollback_tail = None;
+            self.send_scrollback_update().await?;
+        }
+        self.is_scrollback_subscribed = capabilities.scrollback;
+
+        let mut key_events = Vec::<termwiz::input::KeyEvent>::new();
+        for chord in capabilities.keybindings {
+            let chord_description = match &chord.mods {
+                Some(mods) => format!("{mods}+{}", chord.key),
+                None => chord.key.clone(),
+            };
+            let raw = crate::config::input::KeybindingConfigRaw {
+                mods: chord.mods,
+                key: chord.key,
+            };
+            let key_event: termwiz::input::KeyEvent = match raw.try_into() {
+                Ok(key_event) => key_event,
+                Err(error) => {
+                    tracing::warn!(
+                        "Plugin '{}' sent invalid keybinding {chord_description}: {error:?}",
+                        self.tattoy.id
+                    );
+                    self.tattoy
+                        .state
+                        .send_notification(
+                            format!("'{}' plugin keybinding is invalid", self.tattoy.id).as_str(),
+                            crate::tattoys::notifications::message::Level::Warn,
+                            Some(format!("{chord_description} isn't a valid key chord")),
+                            false,
+                        )
+                        .await;
+                    continue;
+                }
+            };
+
+            let is_taken_by_tattoy = self
+                .tattoy
+                .state
+                .keybindings
+                .read()
+                .await
+                .values()
+                .any(|binding| binding == &key_event);
+            let maybe_owner = self
+                .tattoy
+                .state
+                .get_plugin_keybinding_owner(&key_event)
+                .await;
+            let is_taken_by_other_plugin = maybe_owner.is_some_and(|owner| owner != self.tattoy.id);
+
+            if is_taken_by_tattoy || is_taken_by_other_plugin {
+                self.tattoy
+                    .state
+                    .send_notification(
+                        format!("'{}' plugin keybinding conflict", self.tattoy.id).as_str(),
+                        crate::tattoys::notifications::message::Level::Warn,
+                        Some(format!("{chord_description} is already bound")),
+                        false,
+                    )
+                    .await;
+                continue;
+            }
+
+            key_events.push(key_event);
+        }
+
+        self.tattoy
+            .state
+            .plugin_keybindings
+            .write()
+            .await
+            .insert(self.tattoy.id.clone(), key_events);
+
+        Ok(())
+    }
+
+    /// Notify the user when the plugin was built against an incompatible version of the plugin
+    /// protocol.
+    async fn check_protocol_version(&self, plugin_version: u32) {
+        let tattoy_version = tattoy_protocol::PROTOCOL_VERSION;
+        if plugin_version == tattoy_version {
+            return;
+        }
+
+        let advice = if plugin_version < tattoy_version {
+            "The plugin needs updating."
+        } else {
+            "Tattoy needs updating."
+        };
+        let message = format!(
+            "The plugin uses protocol v{plugin_version}, but Tattoy uses v{tattoy_version}. {advice}"
+        );
+        tracing::warn!("Plugin '{}': {message}", self.tattoy.id);
+
+        self.tattoy
+            .state
+            .send_notification(
+                format!("'{}' plugin is incompatible", self.tattoy.id).as_str(),
+                crate::tattoys::notifications::message::Level::Error,
+                Some(message),
+                false,
+            )
+            .await;
+    }
+
+    /// Notify the user when the plugin didn't tell us which version of the plugin protocol it uses,
+    /// which most likely means that it was built against a version from before the handshake.
+    async fn report_unknown_protocol_version(&self, reason: &str) {
+        let message = format!(
+            "{reason} It was probably built for an older version of Tattoy, so it needs updating."
+        );
+        tracing::warn!("Plugin '{}': {message}", self.tattoy.id);
+
+        self.tattoy
+            .state
+            .send_notification(
+                format!("'{}' plugin is incompatible", self.tattoy.id).as_str(),
+                crate::tattoys::notifications::message::Level::Error,
+                Some(message),
+                false,
+            )
+            .await;
+    }
+
+    /// Tick the render
+    async fn render(&mut self, output: tattoy_protocol::PluginOutputMessages) -> Result<()> {
+        self.tattoy.initialise_surface();
+
+        tracing::debug!("Rendering from plugin message");
+        match output {
+            tattoy_protocol::PluginOutputMessages::OutputText {
+                text,
+                coordinates,
+                bg,
+                fg,
+                attributes,
+            } => {
+                self.tattoy.surface.add_styled_text(
+                    coordinates.0.try_into()?,
+                    coordinates.1.try_into()?,
+                    text,
+                    bg,
+                    fg,
+                    attributes.as_ref(),
+                );
+            }
+            tattoy_protocol::PluginOutputMessages::OutputPixels(pixels) => {
+                for pixel in pixels {
+                    self.tattoy.surface.add_pixel(
+                        pixel.coordinates.0.try_into()?,
+                        pixel.coordinates.1.try_into()?,
+                        // TODO: use the terminal palette's default foreground colour
+                        pixel.color.unwrap_or(crate::surface::WHITE),
+                    )?;
+                }
+            }
+            tattoy_protocol::PluginOutputMessages::OutputImage {
+                origin,
+                width,
+                height,
+                pixels,
+            } => {
+                self.tattoy.surface.add_image(
+                    (origin.0.try_into()?, origin.1.try_into()?),
+                    width.try_into()?,
+                    height.try_into()?,
+                    &pixels,
+                )?;
+            }
+            tattoy_protocol::PluginOutputMessages::OutputCells(cells) => {
+                for cell in cells {
+                    self.tattoy.surface.add_styled_text(
+                        cell.coordinates.0.try_into()?,
+                        cell.coordinates.1.try_into()?,
+                        cell.character.to_string(),
+                        cell.bg,
+                        cell.fg,
+                        cell.attributes.as_ref(),
+                    );
+                }
+            }
+
+            // Not render messages, they're handled by `handle_plugin_message()`.
+            tattoy_protocol::PluginOutputMessages::Capabilities(_)
+            | tattoy_protocol::PluginOutputMessages::Notification { .. }
+            | tattoy_protocol::PluginOutputMessages::SendInput(_) => return Ok(()),
+
+            #[expect(
+                clippy::unreachable,
+                reason = "
+                    The plugin protocol specifies `non-exhaustive`, but we are also the protocol definers,
+                    so we won't get hit by unexpeted protocol changes.
+                "
+            )]
+            _ => unreachable!(),
+        }
+
+        self.tattoy.send_output().await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn newly_enabled_plugins() {
+        let previous = vec![
+            config("name = 'running'\npath = '/a'"),
+            config("name = 'disabled'\npath = '/b'\nenabled = false"),
+        ];
+        let reloaded = vec![
+            config("name = 'running'\npath = '/a'\nlayer = 5"),
+            config("name = 'disabled'\npath = '/b'"),
+            config("name = 'new'\npath = '/c'"),
+            config("name = 'new but disabled'\npath = '/d'\nenabled = false"),
+        ];
+
+        let names: Vec<&str> = Config::newly_enabled(&previous, &reloaded, &["running"])
+            .iter()
+            .map(|plugin| plugin.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["disabled", "new"]);
+    }
+
+    #[test]
+    fn plugins_that_failed_to_start_are_restarted_when_fixed() {
+        let previous = vec![
+            config(
+                "name = 'fixed'
+path = '/typo'",
+            ),
+            config(
+                "name = 'still broken'
+path = '/typo'",
+            ),
+            config(
+                "name = 'running'
+path = '/a'",
+            ),
+        ];
+        let reloaded = vec![
+            config(
+                "name = 'fixed'
+path = '/b'",
+            ),
+            config(
+                "name = 'still broken'
+path = '/typo'
+layer = 5",
+            ),
+            config(
+                "name = 'running'
+path = '/c'",
+            ),
+        ];
+
+        let names: Vec<&str> = Config::newly_enabled(&previous, &reloaded, &["running"])
+            .iter()
+            .map(|plugin| plugin.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["fixed"]);
+    }
+
+    #[test]
+    fn plugin_settings() {
+        let plugin = config(
+            "
+            name = 'smokey'
+            path = '/a'
+            args = ['--verbose']
+            env = { SMOKEY_LOG = 'debug' }
+
+            [settings]
+            max_particles = 1000
+            gravity = [0.0, -1.0]
+            ",
+        );
+
+        assert_eq!(plugin.args.unwrap(), vec!["--verbose"]);
+        assert_eq!(plugin.env.unwrap()["SMOKEY_LOG"], "debug");
+        assert_eq!(
+            serde_json::Value::Object(plugin.settings.unwrap()),
+            serde_json::json!({"max_particles": 1000, "gravity": [0.0, -1.0]})
+        );
+    }
+
+    /// The end of a plugin's socket that a test controls, as if it were the plugin daemon.
+    #[cfg(not(target_os = "windows"))]
+    struct Daemon {
+        /// The daemon's side of the socket.
+        _stream: std::os::unix::net::UnixStream,
+        /// The frames that the plugin renders.
+        _output_rx: tokio::sync::mpsc::Receiver<crate::run::FrameUpdate>,
+        /// Where the socket lives.
+        _directory: tempfile::TempDir,
+    }
+
+    /// Start a plugin, returning it along with the receiving end of its rendered frames.
+    #[cfg(not(target_os = "windows"))]
+    async fn new_plugin(
+        plugin_config: &Config,
+    ) -> (Plugin, tokio::sync::mpsc::Receiver<crate::run::FrameUpdate>) {
+        let (protocol_tx, _) = tokio::sync::broadcast::channel(1024);
+        let state = crate::shared_state::SharedState::init(10, 10, protocol_tx)
+            .await
+            .unwrap();
+        let (output_tx, output_rx) = tokio::sync::mpsc::channel(1024);
+        let palette = crate::palette::converter::Palette {
+            map: std::collections::HashMap::default(),
+        };
+        let plugin = Plugin::new(plugin_config, output_tx, palette, state)
+            .await
+            .unwrap();
+        (plugin, output_rx)
+    }
+
+    /// Start a plugin called "test" that's connected to a socket that the test controls.
+    #[cfg(not(target_os = "windows"))]
+    async fn make_plugin(extra_config: &str) -> (Plugin, Daemon) {
+        let directory = tempfile::tempdir().unwrap();
+        let socket = directory.path().join("plugin.sock");
+        let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        let plugin_config = config(&format!(
+            "name = 'test'\nsocket = '{}'\n{extra_config}",
+            socket.display()
+        ));
+
+        let (plugin, output_rx) = new_plugin(&plugin_config).await;
+        let (stream, _) = listener.accept().unwrap();
+
+        let daemon = Daemon {
+            _stream: stream,
+            _output_rx: output_rx,
+            _directory: directory,
+        };
+        (plugin, daemon)
+    }
+
+    fn chord(mods: &str, key: &str) -> tattoy_protocol::KeyChord {
+        tattoy_protocol::KeyChord::builder()
+            .mods(mods.to_owned())
+            .key(key.to_owned())
+            .build()
+    }
+
+    fn key_event(mods: &str, key: &str) -> termwiz::input::KeyEvent {
+        crate::config::input::KeybindingConfigRaw {
+            mods: Some(mods.to_owned()),
+            key: key.to_owned(),
+        }
+        .try_into()
+        .unwrap()
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test]
+    async fn plugins_without_a_protocol_version_are_reported() {
+        let (mut plugin, _daemon) = make_plugin("").await;
+        let mut protocol_rx = plugin.tattoy.state.protocol_tx.subscribe();
+        let capabilities = tattoy_protocol::Capabilities::builder().build();
+        plugin.register_capabilities(capabilities).await.unwrap();
+
+        let mut titles = Vec::new();
+        while let Ok(message) = protocol_rx.try_recv() {
+            if let crate::run::Protocol::Notification(notification) = message {
+                assert_eq!(
+                    notification.level,
+                    crate::tattoys::notifications::message::Level::Error
+                );
+                titles.push(notification.title);
+            }
+        }
+        assert_eq!(titles, vec!["'test' plugin is incompatible".to_owned()]);
+        assert!(plugin.capabilities_deadline.is_none());
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test]
+    async fn running_plugins_that_send_corrupt_messages_are_reaped() {
+        // An impossibly long length prefix, and then the plugin carries on running.
+        let plugin_config = config(
+            r#"
+            name = 'test'
+            path = '/bin/sh'
+            args = ['-c', 'printf "\377\377\377\377"; exec sleep 60']
+            encoding = 'msgpack'
+            "#,
+        );
+        let (mut plugin, _output_rx) = new_plugin(&plugin_config).await;
+
+        let maybe_message = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            plugin.process.parsed_messages_rx.recv(),
+        )
+        .await
+        .unwrap();
+        assert!(maybe_message.is_none());
+
+        assert_eq!(plugin.reap(), "closed STDOUT");
+    }
+
+    #[tokio::test]
+    async fn json_listener_receives_every_message() {
+        let json = r#"{"capabilities": {"name": "test"}}{"output_text": {"text": "foo", "coordinates": [0, 0]}}"#;
+        let mut reader: PluginReader =
+            std::io::BufReader::new(Box::new(std::io::Cursor::new(json.as_bytes().to_vec())));
+        let (parsed_messages_tx, mut parsed_messages_rx) = tokio::sync::mpsc::channel(16);
+
+        Plugin::listener(&mut reader, &parsed_messages_tx)
+            .await
+            .unwrap();
+        drop(parsed_messages_tx);
+
+        assert!(matches!(
+            parsed_messages_rx.recv().await,
+            Some(tattoy_protocol::PluginOutputMessages::Capabilities(_))
+        ));
+        assert!(matches!(
+            parsed_messages_rx.recv().await,
+            Some(tattoy_protocol::PluginOutputMessages::OutputText { .. })
+        ));
+        assert!(parsed_messages_rx.recv().await.is_none());
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test]
+    async fn invalid_keybindings_dont_stop_valid_ones_being_registered() {
+        let (mut plugin, _daemon) = make_plugin("").await;
+        let state = std::sync::Arc::clone(&plugin.tattoy.state);
+        let mut protocol_rx = state.protocol_tx.subscribe();
+        let capabilities = tattoy_protocol::Capabilities::builder()
+            .protocol_version(tattoy_protocol::PROTOCOL_VERSION)
+            .keybindings(vec![chord("ALT", "NotAKey"), chord("ALT", "p")])
+            .build();
+        plugin.register_capabilities(capabilities).await.unwrap();
+
+        assert_eq!(
+            state
+                .get_plugin_keybinding_owner(&key_event("ALT", "p"))
+                .await,
+            Some("test".to_owned())
+        );
+        let mut notification_bodies = Vec::new();
+        while let Ok(message) = protocol_rx.try_recv() {
+            if let crate::run::Protocol::Notification(notification) = message {
+                notification_bodies.extend(notification.body);
+            }
+        }
+        assert_eq!(
+            notification_bodies,
+            vec!["ALT+NotAKey isn't a valid key chord".to_owned()]
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test]
+    async fn stopping_a_plugin_isnt_reported_as_it_exiting() {
+        let (mut plugin, _daemon) = make_plugin("").await;
+        let mut protocol_rx = plugin.tattoy.state.protocol_tx.subscribe();
+
+        plugin.stop(crate::run::Protocol::End).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+        while let Ok(message) = protocol_rx.try_recv() {
+            if let crate::run::Protocol::Notification(notification) = message {
+                panic!("Unexpected notification: {}", notification.title);
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test]
+    async fn disabling_a_plugin_releases_its_keybindings() {
+        let (mut plugin, _daemon) = make_plugin("").await;
+        let capabilities = tattoy_protocol::Capabilities::builder()
+            .protocol_version(tattoy_protocol::PROTOCOL_VERSION)
+            .keybindings(vec![chord("ALT", "p")])
+            .build();
+        plugin.register_capabilities(capabilities).await.unwrap();
+        let state = std::sync::Arc::clone(&plugin.tattoy.state);
+        assert_eq!(
+            state
+                .get_plugin_keybinding_owner(&key_event("ALT", "p"))
+                .await,
+            Some("test".to_owned())
+        );
+
+        let disabled = config("name = 'test'\nsocket = '/a'\nenabled = false");
+        assert!(!plugin.reload_config(&[disabled]).await.unwrap());
+        plugin.disable().await.unwrap();
+
+        assert_eq!(
+            state
+                .get_plugin_keybinding_owner(&key_event("ALT", "p"))
+                .await,
+            None
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test]
+    async fn plugins_that_were_given_up_on_are_restarted_when_fixed() {
+        let broken = config("name = 'test'\npath = '/bin/false'\nmax_restarts = 0");
+        let (mut plugin, _output_rx) = new_plugin(&broken).await;
+        plugin.handle_exit().await.unwrap();
+        assert!(!plugin.is_running);
+        assert!(plugin.restart_at.is_none());
+
+        let unrelated_change = config("name = 'test'\npath = '/bin/false'\nlayer = 5");
+        assert!(plugin.reload_config(&[unrelated_change]).await.unwrap());
+        assert!(!plugin.is_running);
+
+        let fixed = config("name = 'test'\npath = '/bin/sh'\nargs = ['-c', 'exec sleep 60']");
+        assert!(plugin.reload_config(&[fixed]).await.unwrap());
+        assert!(plugin.is_running);
+        assert_eq!(plugin.restart_count, 0);
+        plugin.kill().unwrap();
+    }
+
+    fn scrollback(texts: &[&str]) -> termwiz::surface::Surface {
+        let mut surface = termwiz::surface::Surface::new(5, texts.len());
+        surface.add_change(texts.join("\r\n"));
+        surface
+    }
+
+    fn tail(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|text| format!("{text:<5}")).collect()
+    }
+
+    #[test]
+    fn added_scrollback_lines_are_changed() {
+        let current = scrollback(&["a", "b", "c", "d"]);
+        let first_changed =
+            Plugin::find_first_changed_line(&tail(&["b", "c"]), 3, &current.screen_lines());
+        assert_eq!(first_changed, Some(3));
+    }
+
+    #[test]
+    fn edited_screen_lines_are_changed() {
+        let current = scrollback(&["a", "b", "x", "d"]);
+        let first_changed =
+            Plugin::find_first_changed_line(&tail(&["b", "c"]), 3, &current.screen_lines());
+        assert_eq!(first_changed, Some(2));
+    }
+
+    #[test]
+    fn unchanged_scrollback_has_no_changed_lines() {
+        let current = scrollback(&["a", "b", "c"]);
+        let first_changed =
+            Plugin::find_first_changed_line(&tail(&["b", "c"]), 3, &current.screen_lines());
+        assert_eq!(first_changed, Some(3));
+    }
+
+    #[test]
+    fn shrunk_scrollback_is_sent_entirely() {
+        let current = scrollback(&["a", "b"]);
+        let first_changed =
+            Plugin::find_first_changed_line(&tail(&["b", "c"]), 3, &current.screen_lines());
+        assert_eq!(first_changed, None);
     }
 }
@@ -21,6 +21,10 @@ pub(crate) struct Config {
     pub opacity: f32,
     /// The layer (or z-index) into which the shaders are rendered.
     pub layer: i16,
+    /// How the shader's colours are combined with the layers below it.
+    pub blend_mode: crate::blender::BlendMode,
+    /// The shader's frame rate. Defaults to the global frame rate.
+    pub frame_rate: Option<u32>,
     /// The shader is still sent and run on the GPU but it's not rendered to a layer on the
     /// terminal. This is most likely useful in conjunction with `render_shader_colours_to_text`,
     /// as "contents" of the shader are rendered via the terminal's text.
@@ -46,6 +50,8 @@ impl Default for Config {
             .into(),
             opacity: 0.75,
             layer: -10,
+            blend_mode: crate::blender::BlendMode::Normal,
+            frame_rate: None,
             render: true,
             upload_tty_as_pixels: true,
             render_shader_colours_to_text: false,
@@ -100,6 +106,10 @@ impl crate::tattoys::gpu::shaderer::Shaderer for Shaders {
         self.tattoy().state.config.read().await.shader.opacity
     }
 
+    async fn get_blend_mode(&self) -> crate::blender::BlendMode {
+        self.tattoy().state.config.read().await.shader.blend_mode
+    }
+
     /// Instantiate
     async fn new(
         output_channel: tokio::sync::mpsc::Sender<crate::run::FrameUpdate>,
@@ -10,12 +10,16 @@ pub(crate) struct Tattoyer {
     pub layer: i16,
     /// The transparency of layer.
     pub opacity: f32,
+    /// How the layer's colours are combined with the layers below it.
+    pub blend_mode: crate::blender::BlendMode,
     /// The application shared state
     pub state: std::sync::Arc<crate::shared_state::SharedState>,
     /// A channel to send final rendered output.
     pub output_channel: tokio::sync::mpsc::Sender<crate::run::FrameUpdate>,
     /// The surface on which to construct this tattoy's frame.
     pub surface: crate::surface::Surface,
+    /// The last frame sent to the renderer. Used to work out which cells have changed.
+    pub previous_surface: Option<crate::surface::Surface>,
     /// TTY width
     pub width: u16,
     /// TTY height
@@ -26,6 +30,10 @@ pub(crate) struct Tattoyer {
     pub screen: shadow_terminal::output::native::CompleteScreen,
     /// The target frame rate.
     pub target_frame_rate: u32,
+    /// The user's config for slowing down animations when the terminal is idle.
+    pub idle: crate::config::main::Idle,
+    /// The time of the last PTY output or user input.
+    pub last_activity_at: tokio::time::Instant,
     /// The time at which the previous frame was rendererd.
     pub last_frame_tick: tokio::time::Instant,
     /// The last known position of an active scroll.
@@ -42,19 +50,26 @@ impl Tattoyer {
         output_channel: tokio::sync::mpsc::Sender<crate::run::FrameUpdate>,
     ) -> Self {
         let tty_size = state.get_tty_size().await;
-        let target_frame_rate = state.config.read().await.frame_rate;
+        let config = state.config.read().await;
+        let target_frame_rate = config.frame_rate_for(&id);
+        let idle = config.idle.clone();
+        drop(config);
         Self {
             id: id.clone(),
             layer,
             opacity,
+            blend_mode: crate::blender::BlendMode::Normal,
             state,
             output_channel,
             surface: crate::surface::Surface::new(id, 0, 0, layer, opacity),
+            previous_surface: None,
             width: tty_size.width,
             height: tty_size.height,
             scrollback: shadow_terminal::output::native::CompleteScrollback::default(),
             screen: shadow_terminal::output::native::CompleteScreen::default(),
             target_frame_rate,
+            idle,
+            last_activity_at: tokio::time::Instant::now(),
             last_frame_tick: tokio::time::Instant::now(),
             last_scroll_position: 0,
         }
@@ -69,6 +84,26 @@ impl Tattoyer {
             self.layer,
             self.opacity,
         );
+        self.surface.blend_mode = self.blend_mode;
+    }
+
+    /// Create an empty surface ready for building a new frame that only covers part of the
+    /// terminal, like a status bar or a badge. It is placed according to the region.
+    pub fn initialise_region_surface(
+        &mut self,
+        width: u16,
+        height: u16,
+        region: crate::surface::Region,
+    ) {
+        self.surface = crate::surface::Surface::new(
+            self.id.clone(),
+            width.into(),
+            height.into(),
+            self.layer,
+            self.opacity,
+        );
+        self.surface.blend_mode = self.blend_mode;
+        self.surface.region = Some(region);
     }
 
     /// Keep track of the size of the underlying terminal.
@@ -93,10 +128,18 @@ impl Tattoyer {
         )]
         match message {
             crate::run::Protocol::Resize { width, height } => {
+                self.last_activity_at = tokio::time::Instant::now();
                 self.set_tty_size(width, height);
             }
-            crate::run::Protocol::Output(output) => self.handle_pty_output(output)?,
-            crate::run::Protocol::Config(config) => self.target_frame_rate = config.frame_rate,
+            crate::run::Protocol::Output(output) => {
+                self.last_activity_at = tokio::time::Instant::now();
+                self.handle_pty_output(output)?;
+            }
+            crate::run::Protocol::Input(_) => self.last_activity_at = tokio::time::Instant::now(),
+            crate::run::Protocol::Config(config) => {
+                self.target_frame_rate = config.frame_rate_for(&self.id);
+                self.idle = config.idle;
+            }
             _ => (),
         }
 
@@ -136,9 +179,12 @@ impl Tattoyer {
                     self.scrollback.position = scrollback_diff.position;
                 }
                 shadow_terminal::output::native::SurfaceDiff::Screen(screen_diff) => {
-                    self.screen
-                        .surface
-                        .resize(screen_diff.size.0, screen_diff.size.1);
+                    // Resizing marks every line as changed, even when the size is the same.
+                    if self.screen.surface.dimensions() != screen_diff.size {
+                        self.screen
+                            .surface
+                            .resize(screen_diff.size.0, screen_diff.size.1);
+                    }
                     self.set_tty_size(
                         screen_diff.size.0.try_into()?,
                         screen_diff.size.1.try_into()?,
@@ -166,8 +212,18 @@ impl Tattoyer {
 
     /// Send the final surface to the main renderer.
     pub(crate) async fn send_output(&mut self) -> Result<()> {
+        // Working out the damage here, rather than in the renderer, keeps the cost of diffing out
+        // of the render loop.
+        self.surface.damage = self
+            .previous_surface
+            .as_ref()
+            .map_or(crate::surface::Damage::Full, |previous| {
+                self.surface.damage_since(previous)
+            });
+        let surface = self.surface.clone();
+        self.previous_surface = Some(surface.clone());
         self.output_channel
-            .send(crate::run::FrameUpdate::TattoySurface(self.surface.clone()))
+            .send(crate::run::FrameUpdate::TattoySurface(surface))
             .await?;
 
         self.last_scroll_position = self.scrollback.position;
@@ -183,16 +239,34 @@ impl Tattoyer {
         self.send_output().await
     }
 
-    /// Sleep until the next frame render is due.
+    /// Sleep until the next frame render is due. Frames are slowed down when the terminal is
+    /// idle, and when the renderer can't keep up.
     pub async fn sleep_until_next_frame_tick(&mut self) {
-        let target = crate::renderer::ONE_MICROSECOND.wrapping_div(self.target_frame_rate.into());
-        let target_frame_rate_micro = std::time::Duration::from_micros(target);
-        if let Some(wait) = target_frame_rate_micro.checked_sub(self.last_frame_tick.elapsed()) {
+        let frame_rate = if self.is_idle() {
+            self.target_frame_rate.min(self.idle.frame_rate)
+        } else {
+            self.target_frame_rate
+        };
+        let throttle = *self.state.throttle.read().await;
+        let interval = Self::frame_interval(frame_rate, throttle);
+        if let Some(wait) = interval.checked_sub(self.last_frame_tick.elapsed()) {
             tokio::time::sleep(wait).await;
         }
         self.last_frame_tick = tokio::time::Instant::now();
     }
 
+    /// Has there been no PTY output or user input for a while?
+    fn is_idle(&self) -> bool {
+        self.idle.enabled
+            && self.last_activity_at.elapsed() >= std::time::Duration::from_secs(self.idle.timeout)
+    }
+
+    /// The time between frames at the given frame rate, stretched by the renderer's throttle.
+    fn frame_interval(frame_rate: u32, throttle: f32) -> std::time::Duration {
+        let interval = crate::renderer::ONE_MICROSECOND.wrapping_div(frame_rate.max(1).into());
+        std::time::Duration::from_micros(interval).mul_f32(throttle.max(1.0))
+    }
+
     /// Check if the scrollback output has changed.
     pub const fn is_scrollback_output_changed(message: &crate::run::Protocol) -> bool {
         #[expect(
@@ -363,3 +437,24 @@ impl Tattoyer {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn frame_intervals() {
+        assert_eq!(
+            Tattoyer::frame_interval(30, 1.0),
+            std::time::Duration::from_micros(33_333)
+        );
+        assert_eq!(
+            Tattoyer::frame_interval(2, 1.5),
+            std::time::Duration::from_millis(750)
+        );
+        assert_eq!(
+            Tattoyer::frame_interval(0, 0.5),
+            std::time::Duration::from_secs(1)
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Plugins compiled to WebAssembly. They run in an embedded `wasmtime` runtime that gives them no
+//! access to the filesystem, the network or the user's environment. They talk to Tattoy over
+//! their WASI STDIO, with exactly the same messages as native plugins.
+
+use color_eyre::eyre::Result;
+
+/// The maximum number of bytes from the plugin that can be waiting to be written to its STDOUT or
+/// STDERR pipe.
+const WRITE_BUDGET: usize = 1024 * 1024;
+
+/// How long to wait for a plugin to finish after it has been interrupted.
+const INTERRUPT_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// The streams for talking to a WebAssembly plugin: its STDOUT, STDIN and STDERR.
+pub(crate) type Streams = (
+    Instance,
+    Box<dyn std::io::Read + Send>,
+    Box<dyn std::io::Write + Send>,
+    Box<dyn std::io::Read + Send>,
+);
+
+/// A running WebAssembly plugin.
+pub(crate) struct Instance {
+    /// The `wasmtime` engine that the plugin runs on. Used to interrupt the plugin.
+    engine: wasmtime::Engine,
+    /// The thread that the plugin runs on. Returns the plugin's exit code.
+    thread: Option<std::thread::JoinHandle<Result<i32>>>,
+}
+
+impl Instance {
+    /// Load the plugin's WebAssembly module and start running it on its own thread.
+    pub(crate) fn spawn(
+        name: &str,
+        path: &std::path::Path,
+        args: &[String],
+        env: &[(String, String)],
+    ) -> Result<Streams> {
+        tracing::debug!(
+            "Starting WebAssembly plugin, '{name}', from: {}",
+            path.display()
+        );
+
+        let mut wasmtime_config = wasmtime::Config::new();
+        // Allows us to stop plugins that never return control to us.
+        wasmtime_config.epoch_interruption(true);
+        let engine =
+            wasmtime::Engine::new(&wasmtime_config).map_err(color_eyre::eyre::Report::msg)?;
+        let module = match wasmtime::Module::from_file(&engine, path) {
+            Ok(module) => module,
+            Err(error) => color_eyre::eyre::bail!(
+                "Couldn't load WebAssembly plugin at {}: {error:#}",
+                path.display()
+            ),
+        };
+
+        let (stdin_reader, stdin_writer) = std::io::pipe()?;
+        let (stdout_reader, stdout_writer) = std::io::pipe()?;
+        let (stderr_reader, stderr_writer) = std::io::pipe()?;
+
+        // Nothing is shared with the plugin apart from its STDIO, and the arguments and environment
+        // variables from its config. No preopened directories, none of the user's own environment
+        // variables and no network.
+        let wasi = wasmtime_wasi::WasiCtxBuilder::new()
+            .arg(name)
+            .args(args)
+            .envs(env)
+            .stdin(wasmtime_wasi::AsyncStdinStream::new(
+                wasmtime_wasi::pipe::AsyncReadStream::new(Self::async_file(stdin_reader.into())),
+            ))
+            .stdout(wasmtime_wasi::AsyncStdoutStream::new(
+                wasmtime_wasi::pipe::AsyncWriteStream::new(
+                    WRITE_BUDGET,
+                    Self::async_file(stdout_writer.into()),
+                ),
+            ))
+            .stderr(wasmtime_wasi::AsyncStdoutStream::new(
+                wasmtime_wasi::pipe::AsyncWriteStream::new(
+                    WRITE_BUDGET,
+                    Self::async_file(stderr_writer.into()),
+                ),
+            ))
+            .build_p1();
+
+        let thread_engine = engine.clone();
+        let thread = std::thread::spawn(move || Self::run(&thread_engine, &module, wasi));
+
+        Ok((
+            Self {
+                engine,
+                thread: Some(thread),
+            },
+            Box::new(stdout_reader),
+            Box::new(stdin_writer),
+            Box::new(stderr_reader),
+        ))
+    }
+
+    /// Run the plugin's `_start` function until the plugin exits.
+    fn run(
+        engine: &wasmtime::Engine,
+        module: &wasmtime::Module,
+        wasi: wasmtime_wasi::preview1::WasiP1Ctx,
+    ) -> Result<i32> {
+        let mut linker = wasmtime::Linker::new(engine);
+        wasmtime_wasi::preview1::add_to_linker_sync(
+            &mut linker,
+            |context: &mut wasmtime_wasi::preview1::WasiP1Ctx| context,
+        )
+        .map_err(color_eyre::eyre::Report::msg)?;
+
+        let mut store = wasmtime::Store::new(engine, wasi);
+        // Trap as soon as the engine's epoch is incremented, see `Self::kill()`.
+        store.set_epoch_deadline(1);
+
+        let instance = linker
+            .instantiate(&mut store, module)
+            .map_err(color_eyre::eyre::Report::msg)?;
+        let start = instance
+            .get_typed_func::<(), ()>(&mut store, "_start")
+            .map_err(color_eyre::eyre::Report::msg)?;
+
+        match start.call(&mut store, ()) {
+            Ok(()) => Ok(0),
+            Err(error) => match error.downcast_ref::<wasmtime_wasi::I32Exit>() {
+                Some(exit) => Ok(exit.0),
+                None => Err(color_eyre::eyre::Report::msg(error)),
+            },
+        }
+    }
+
+    /// Convert one end of a pipe into a file that `wasmtime` can read from or write to
+    /// asynchronously.
+    #[cfg(not(target_os = "windows"))]
+    fn async_file(pipe: std::os::fd::OwnedFd) -> tokio::fs::File {
+        tokio::fs::File::from_std(std::fs::File::from(pipe))
+    }
+
+    /// Convert one end of a pipe into a file that `wasmtime` can read from or write to
+    /// asynchronously.
+    #[cfg(target_os = "windows")]
+    fn async_file(pipe: std::os::windows::io::OwnedHandle) -> tokio::fs::File {
+        tokio::fs::File::from_std(std::fs::File::from(pipe))
+    }
+
+    /// Stop the plugin the next time that it runs any of its own code.
+    pub(crate) fn kill(&self) {
+        self.engine.increment_epoch();
+    }
+
+    /// Make sure the plugin has stopped and describe how it exited.
+    pub(crate) fn reap(&mut self) -> String {
+        let Some(thread) = self.thread.take() else {
+            return "unknown status (already reaped)".to_owned();
+        };
+
+        if !thread.is_finished() {
+            // The plugin closed its STDOUT but is still running, so it's of no use to us.
+            self.kill();
+            let started_waiting = std::time::Instant::now();
+            while !thread.is_finished() && started_waiting.elapsed() < INTERRUPT_TIMEOUT {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            if !thread.is_finished() {
+                return "closed STDOUT".to_owned();
+            }
+        }
+
+        match thread.join() {
+            Ok(Ok(code)) => format!("exit status: {code}"),
+            Ok(Err(error)) => format!("error ({error})"),
+            Err(_) => "a panic".to_owned(),
+        }
+    }
+}
@@ -32,6 +32,11 @@ impl crate::terminal_proxy::proxy::Proxy {
             String::from_utf8_lossy(&bytes)
         );
 
+        self.write_to_pty(&bytes).await
+    }
+
+    /// Write bytes to the underlying PTY, as if the user had typed them.
+    pub async fn write_to_pty(&self, bytes: &[u8]) -> Result<()> {
         for chunk in bytes.chunks(128) {
             let mut buffer: crate::raw_input::BytesFromSTDIN = [0; 128];
             for (i, chunk_byte) in chunk.iter().enumerate() {
@@ -56,6 +61,7 @@ impl crate::terminal_proxy::proxy::Proxy {
         let is_input_event = match event {
             termwiz::input::InputEvent::Key(key_event) => {
                 self.handle_tattoy_key_event(key_event).await?
+                    || self.is_key_event_claimed_by_plugin(key_event).await
             }
             termwiz::input::InputEvent::Mouse(mouse_event) => {
                 self.handle_mouse_scrolling_input(mouse_event).await?
@@ -141,9 +147,28 @@ impl crate::terminal_proxy::proxy::Proxy {
                     ))?;
                 Ok(true)
             }
+            crate::config::input::KeybindingAction::TogglePluginLog => {
+                self.tattoy_protocol
+                    .send(crate::run::Protocol::KeybindEvent(
+                        crate::config::input::KeybindingAction::TogglePluginLog,
+                    ))?;
+                Ok(true)
+            }
         }
     }
 
+    /// Plugins can claim key events for themselves, in which case the plugin receives the event
+    /// and it shouldn't be forwarded to the PTY.
+    async fn is_key_event_claimed_by_plugin(&self, key_event: &termwiz::input::KeyEvent) -> bool {
+        let maybe_owner = self.state.get_plugin_keybinding_owner(key_event).await;
+        if let Some(owner) = maybe_owner {
+            tracing::trace!("Key event claimed by plugin '{owner}': {key_event:?}");
+            return true;
+        }
+
+        false
+    }
+
     /// Because Tattoy is a wrapper around a headless, in-memory terminal, it can't rely on the
     /// user's actual terminal (Kitty, Alacritty, iTerm, etc) to do scrolling. So Tattoy forwards
     /// scrolling events to the shadow terminal and renders its own scrollbars etc.
@@ -12,7 +12,7 @@ pub(crate) struct Proxy {
     /// Shared app state
     pub state: Arc<SharedState>,
     /// A headless Wezterm terminal running entirely in memory.
-    pub shadow_terminal: shadow_terminal::active_terminal::ActiveTerminal,
+    pub shadow_terminal: super::tapped_terminal::TappedTerminal,
     /// A channel for output updates from the shadow terminal screen.
     surfaces_tx: tokio::sync::mpsc::Sender<crate::run::FrameUpdate>,
     /// The Tattoy protocol
@@ -28,7 +28,7 @@ impl Proxy {
     /// content of the shadow terminal.
     async fn new(
         state: Arc<SharedState>,
-        shadow_terminal: shadow_terminal::active_terminal::ActiveTerminal,
+        shadow_terminal: super::tapped_terminal::TappedTerminal,
         surfaces_tx: tokio::sync::mpsc::Sender<crate::run::FrameUpdate>,
         tattoy_protocol: tokio::sync::broadcast::Sender<crate::run::Protocol>,
     ) -> Result<Self> {
@@ -48,7 +48,8 @@ impl Proxy {
         tattoy_protocol: tokio::sync::broadcast::Sender<crate::run::Protocol>,
         config: shadow_terminal::shadow_terminal::Config,
     ) -> Result<()> {
-        let shadow_terminal = shadow_terminal::active_terminal::ActiveTerminal::start(config);
+        let shadow_terminal =
+            super::tapped_terminal::TappedTerminal::start(config, Arc::clone(&state));
 
         let mut tattoy_protocol_rx = tattoy_protocol.subscribe();
         let mut proxy =
@@ -214,6 +215,9 @@ impl Proxy {
             crate::run::Protocol::Input(input) => {
                 self.handle_input(&input).await?;
             }
+            crate::run::Protocol::PTYInput(bytes) => {
+                self.write_to_pty(&bytes).await?;
+            }
             _ => (),
         }
 
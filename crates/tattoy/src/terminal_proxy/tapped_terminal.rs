@@ -0,0 +1,140 @@
+//! Shadow Terminal's `ActiveTerminal`, but with a tap on the PTY's raw output. Wezterm parses the
+//! PTY's output inside the shadow terminal's task, so things that it doesn't pass on, like the
+//! shell's working directory, are otherwise lost to us.
+
+use std::sync::Arc;
+
+use tracing::Instrument as _;
+
+use crate::shared_state::SharedState;
+
+/// The result of sending a control message to the shadow terminal.
+type ControlResult =
+    Result<usize, tokio::sync::broadcast::error::SendError<shadow_terminal::Protocol>>;
+
+/// A shadow terminal running in a Tokio task. We interact with it and the PTY through Tokio
+/// channels.
+pub(crate) struct TappedTerminal {
+    /// The task handle to the actively running shadow terminal.
+    pub task_handle: tokio::task::JoinHandle<()>,
+    /// Receives updates of the underlying terminal's surfaces.
+    pub surface_output_rx: tokio::sync::mpsc::Receiver<shadow_terminal::output::native::Output>,
+    /// Forwards bytes to the underlying PTY's STDIN.
+    pty_input_tx: tokio::sync::mpsc::Sender<shadow_terminal::pty::BytesFromSTDIN>,
+    /// Sends protocol messages that control the shadow terminal and PTY. For example; resizing
+    /// and shutting down.
+    control_tx: tokio::sync::broadcast::Sender<shadow_terminal::Protocol>,
+}
+
+impl TappedTerminal {
+    /// Start a shadow terminal running in a Tokio task.
+    pub fn start(
+        config: shadow_terminal::shadow_terminal::Config,
+        state: Arc<SharedState>,
+    ) -> Self {
+        tracing::debug!("Starting shadow terminal...");
+        let (pty_input_tx, pty_input_rx) = tokio::sync::mpsc::channel(1);
+        let (surface_output_tx, surface_output_rx) = tokio::sync::mpsc::channel(1);
+
+        let mut shadow_terminal =
+            shadow_terminal::shadow_terminal::ShadowTerminal::new(config, surface_output_tx);
+        let control_tx = shadow_terminal.channels.control_tx.clone();
+
+        // The PTY is given a clone of this sender when the shadow terminal starts running, so
+        // swapping it now puts us between the PTY and Wezterm.
+        let (tap_tx, tap_rx) = tokio::sync::mpsc::channel(1);
+        let pty_output_tx = std::mem::replace(&mut shadow_terminal.channels.output_tx, tap_tx);
+        tokio::spawn(Self::tap(tap_rx, pty_output_tx, state).in_current_span());
+
+        let current_span = tracing::Span::current();
+        let task_handle = tokio::spawn(async move {
+            shadow_terminal
+                .run(pty_input_rx)
+                .instrument(current_span)
+                .await;
+        });
+        tracing::debug!("Shadow terminal started.");
+
+        Self {
+            task_handle,
+            surface_output_rx,
+            pty_input_tx,
+            control_tx,
+        }
+    }
+
+    /// Look for the shell's working directory in the PTY's output, then pass the output on to the
+    /// shadow terminal.
+    async fn tap(
+        mut tap_rx: tokio::sync::mpsc::Receiver<shadow_terminal::pty::BytesFromPTY>,
+        pty_output_tx: tokio::sync::mpsc::Sender<shadow_terminal::pty::BytesFromPTY>,
+        state: Arc<SharedState>,
+    ) {
+        let mut parser = super::working_directory::Parser::default();
+        while let Some(bytes) = tap_rx.recv().await {
+            let length = bytes
+                .iter()
+                .position(|byte| *byte == 0)
+                .unwrap_or(bytes.len());
+            let maybe_directory = bytes.get(..length).and_then(|output| parser.parse(output));
+            if let Some(directory) = maybe_directory {
+                tracing::debug!("Shell's working directory changed to: {directory}");
+                state.set_working_directory(directory).await;
+            }
+
+            if pty_output_tx.send(bytes).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Send input directly into the underlying PTY process.
+    pub async fn send_input(
+        &self,
+        bytes: shadow_terminal::pty::BytesFromSTDIN,
+    ) -> Result<(), tokio::sync::mpsc::error::SendError<shadow_terminal::pty::BytesFromSTDIN>> {
+        self.pty_input_tx.send(bytes).await
+    }
+
+    /// End all loops and send OS kill signals to the underlying PTY.
+    pub fn kill(&self) -> ControlResult {
+        tracing::debug!("`kill()` called on `TappedTerminal`");
+        self.control_tx.send(shadow_terminal::Protocol::End)
+    }
+
+    /// Resize the shadow terminal.
+    pub fn resize(&self, width: u16, height: u16) -> ControlResult {
+        self.control_tx
+            .send(shadow_terminal::Protocol::Resize { width, height })
+    }
+
+    /// Scroll the shadow terminal up.
+    pub fn scroll_up(&self) -> ControlResult {
+        self.control_tx.send(shadow_terminal::Protocol::Scroll(
+            shadow_terminal::Scroll::Up,
+        ))
+    }
+
+    /// Scroll the shadow terminal down.
+    pub fn scroll_down(&self) -> ControlResult {
+        self.control_tx.send(shadow_terminal::Protocol::Scroll(
+            shadow_terminal::Scroll::Down,
+        ))
+    }
+
+    /// Cancel scrolling, and return the scroll to normal.
+    pub fn scroll_cancel(&self) -> ControlResult {
+        self.control_tx.send(shadow_terminal::Protocol::Scroll(
+            shadow_terminal::Scroll::Cancel,
+        ))
+    }
+}
+
+impl Drop for TappedTerminal {
+    fn drop(&mut self) {
+        let result = self.kill();
+        if let Err(error) = result {
+            tracing::debug!("`TappedTerminal.drop()`: {error:?}");
+        }
+    }
+}
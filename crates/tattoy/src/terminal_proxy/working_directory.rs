@@ -0,0 +1,159 @@
+//! Pick out the shell's current working directory from the PTY's output. Shells, or their
+//! prompts, report it with the OSC 7 escape sequence, eg: `\x1b]7;file://hostname/home/user\x07`.
+
+/// The start of an OSC 7 sequence.
+const OSC_7_START: &[u8] = b"\x1b]7;";
+
+/// The longest OSC 7 sequence that we'll wait to be completed. Anything longer is most likely not
+/// an OSC 7 sequence at all, but some unterminated garbage.
+const MAX_SEQUENCE_LENGTH: usize = 4096;
+
+/// Finds OSC 7 sequences in the PTY's output. The PTY's output arrives in chunks, so a sequence
+/// can be split across chunks. Therefore the end of a chunk that might be the start of a sequence
+/// is kept until the next chunk arrives.
+#[derive(Default)]
+pub(crate) struct Parser {
+    /// Output that might be part of an OSC 7 sequence.
+    pending: Vec<u8>,
+}
+
+impl Parser {
+    /// Parse the next chunk of PTY output. Returns the most recent working directory in it, if
+    /// there is one.
+    pub fn parse(&mut self, bytes: &[u8]) -> Option<String> {
+        self.pending.extend_from_slice(bytes);
+
+        let mut maybe_directory = None;
+        loop {
+            let Some(start) = find(&self.pending, OSC_7_START) else {
+                let kept = Self::partial_start_length(&self.pending);
+                self.pending.drain(..self.pending.len() - kept);
+                break;
+            };
+
+            let payload_start = start + OSC_7_START.len();
+            let payload = self.pending.get(payload_start..).unwrap_or_default();
+            let Some((payload_length, terminator_length)) = Self::find_terminator(payload) else {
+                self.pending.drain(..start);
+                if self.pending.len() > MAX_SEQUENCE_LENGTH {
+                    self.pending.clear();
+                }
+                break;
+            };
+
+            if let Some(directory) = payload.get(..payload_length).and_then(Self::parse_file_url) {
+                maybe_directory = Some(directory);
+            }
+            self.pending
+                .drain(..payload_start + payload_length + terminator_length);
+        }
+
+        maybe_directory
+    }
+
+    /// The length of the end of the output that could be the beginning of an OSC 7 sequence.
+    fn partial_start_length(bytes: &[u8]) -> usize {
+        (1..OSC_7_START.len())
+            .rev()
+            .find(|length| {
+                OSC_7_START
+                    .get(..*length)
+                    .is_some_and(|start| bytes.ends_with(start))
+            })
+            .unwrap_or(0)
+    }
+
+    /// Find the end of an OSC sequence's payload. It's either ended with a BEL or with an ST
+    /// (`ESC \`). Returns the length of the payload and of the terminator.
+    fn find_terminator(payload: &[u8]) -> Option<(usize, usize)> {
+        payload
+            .iter()
+            .enumerate()
+            .find_map(|(index, byte)| match byte {
+                b'\x07' => Some((index, 1)),
+                b'\x1b' if payload.get(index + 1) == Some(&b'\\') => Some((index, 2)),
+                _ => None,
+            })
+    }
+
+    /// Convert a `file://hostname/path` URL to just its path.
+    fn parse_file_url(url: &[u8]) -> Option<String> {
+        let without_scheme = url.strip_prefix(b"file://")?;
+        let path_start = without_scheme.iter().position(|byte| *byte == b'/')?;
+        let path = without_scheme.get(path_start..)?;
+        String::from_utf8(percent_decode(path)).ok()
+    }
+}
+
+/// Find the first occurence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Decode the `%XX` escapes in a URL's path.
+fn percent_decode(bytes: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while let Some(byte) = bytes.get(index) {
+        let maybe_escaped = (*byte == b'%')
+            .then(|| bytes.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(escaped) = maybe_escaped {
+            decoded.push(escaped);
+            index += 3;
+        } else {
+            decoded.push(*byte);
+            index += 1;
+        }
+    }
+    decoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_directories_terminated_by_bel_or_st() {
+        let mut parser = Parser::default();
+        assert_eq!(
+            parser.parse(b"$ \x1b]7;file://host/home/me\x07ls\r\n"),
+            Some("/home/me".to_owned())
+        );
+        assert_eq!(
+            parser.parse(b"\x1b]7;file://host/tmp\x1b\\$ "),
+            Some("/tmp".to_owned())
+        );
+        assert_eq!(parser.parse(b"no directory here"), None);
+    }
+
+    #[test]
+    fn finds_directories_split_across_chunks() {
+        let mut parser = Parser::default();
+        assert_eq!(parser.parse(b"output\x1b]"), None);
+        assert_eq!(parser.parse(b"7;file://host/ho"), None);
+        assert_eq!(parser.parse(b"me/me\x07$ "), Some("/home/me".to_owned()));
+    }
+
+    #[test]
+    fn decodes_escaped_paths() {
+        let mut parser = Parser::default();
+        assert_eq!(
+            parser.parse(b"\x1b]7;file://host/home/me/My%20Files\x07"),
+            Some("/home/me/My Files".to_owned())
+        );
+    }
+
+    #[test]
+    fn the_most_recent_directory_wins() {
+        let mut parser = Parser::default();
+        assert_eq!(
+            parser.parse(b"\x1b]7;file://host/a\x07\x1b]7;file://host/b\x07"),
+            Some("/b".to_owned())
+        );
+    }
+}